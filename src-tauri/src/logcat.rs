@@ -0,0 +1,178 @@
+//! `adb logcat` written straight to disk, for overnight captures that are
+//! impractical to stream through the webview.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Stdio};
+use std::sync::{Mutex, PoisonError};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+use crate::{find_in_path, hidden_command, SearchPath};
+
+/// Running captures by device serial.
+#[derive(Default)]
+pub struct LogcatCaptures(Mutex<HashMap<String, Child>>);
+
+/// Payload of the `logcat-file` event, sent whenever a capture opens a new
+/// file and once more with `finished` when it ends.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LogcatFile {
+    device_id: String,
+    path: String,
+    finished: bool,
+    /// Why a capture ended on its own, e.g. adb's "device offline", `None`
+    /// after stop_logcat_to_file
+    error: Option<String>,
+}
+
+/// "capture.log" -> "capture.1.log", the first file is `dest` itself.
+fn rotated_path(dest: &Path, index: u32) -> PathBuf {
+    if index == 0 {
+        return dest.to_path_buf();
+    }
+    let stem = dest.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match dest.extension() {
+        Some(extension) => format!("{stem}.{index}.{}", extension.to_string_lossy()),
+        None => format!("{stem}.{index}"),
+    };
+    dest.with_file_name(name)
+}
+
+/// Starts `adb -s <device_id> logcat` into `dest`. Once a file would grow past
+/// `max_bytes` the capture continues in "<name>.1.<ext>", "<name>.2.<ext>"...
+/// so nothing captured is ever deleted.
+#[tauri::command]
+pub fn start_logcat_to_file(
+    app: AppHandle,
+    captures: State<LogcatCaptures>,
+    search_path: State<SearchPath>,
+    device_id: String,
+    dest: String,
+    max_bytes: u64,
+) -> Result<(), String> {
+    if max_bytes == 0 {
+        return Err("max_bytes must be greater than 0".to_string());
+    }
+    let mut running = captures.0.lock().unwrap_or_else(PoisonError::into_inner);
+    if running.contains_key(&device_id) {
+        return Err(format!("logcat of {device_id} is already being captured"));
+    }
+    let search_path = search_path.get();
+    let adb = find_in_path("adb", &search_path).ok_or("adb was not found in PATH")?;
+    let dest = PathBuf::from(dest);
+    let file = File::create(&dest).map_err(|error| format!("{}: {error}", dest.display()))?;
+    let mut child = hidden_command(&adb, &search_path)
+        .args(["-s", &device_id, "logcat"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("failed to run {}: {error}", adb.display()))?;
+    let stdout = child.stdout.take().ok_or("logcat has no stdout")?;
+    let stderr = child.stderr.take().ok_or("logcat has no stderr")?;
+    let child_id = child.id();
+    running.insert(device_id.clone(), child);
+    drop(running);
+
+    // read apart from stdout so a chatty stderr can't stall the capture
+    let stderr = std::thread::spawn(move || {
+        let mut output = String::new();
+        let _ = BufReader::new(stderr).read_to_string(&mut output);
+        output
+    });
+    std::thread::spawn(move || {
+        let emit = |path: &Path, finished: bool, error: Option<String>| {
+            let _ = app.emit_all(
+                "logcat-file",
+                LogcatFile {
+                    device_id: device_id.clone(),
+                    path: path.to_string_lossy().into_owned(),
+                    finished,
+                    error,
+                },
+            );
+        };
+        let mut failure = None;
+        let mut index = 0;
+        let mut path = dest.clone();
+        let mut writer = BufWriter::new(file);
+        let mut written = 0u64;
+        emit(&path, false, None);
+        for line in BufReader::new(stdout).split(b'\n') {
+            let Ok(mut line) = line else {
+                break;
+            };
+            line.push(b'\n');
+            if written > 0 && written + line.len() as u64 > max_bytes {
+                let _ = writer.flush();
+                index += 1;
+                path = rotated_path(&dest, index);
+                match File::create(&path) {
+                    Ok(file) => writer = BufWriter::new(file),
+                    Err(error) => {
+                        failure = Some(format!("{}: {error}", path.display()));
+                        break;
+                    }
+                }
+                written = 0;
+                emit(&path, false, None);
+            }
+            if let Err(error) = writer.write_all(&line) {
+                failure = Some(format!("{}: {error}", path.display()));
+                break;
+            }
+            written += line.len() as u64;
+        }
+        let _ = writer.flush();
+        // the device went away or writing failed, stop_logcat_to_file has
+        // already removed the entry otherwise
+        let captures = app.state::<LogcatCaptures>();
+        let mut running = captures.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let stopped = running.get(&device_id).map(Child::id) != Some(child_id);
+        if !stopped {
+            if let Some(mut child) = running.remove(&device_id) {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+        }
+        drop(running);
+        // adb has exited or was killed by now, so stderr is at its end
+        let stderr = stderr.join().unwrap_or_default();
+        let error = if stopped {
+            None
+        } else {
+            failure.or_else(|| {
+                let stderr = stderr.trim();
+                Some(if stderr.is_empty() {
+                    "adb logcat exited".to_string()
+                } else {
+                    stderr.to_string()
+                })
+            })
+        };
+        emit(&path, true, error);
+    });
+    Ok(())
+}
+
+/// Ends the capture of `device_id`, its last file is flushed before the
+/// final `logcat-file` event.
+#[tauri::command]
+pub fn stop_logcat_to_file(
+    captures: State<LogcatCaptures>,
+    device_id: String,
+) -> Result<(), String> {
+    let child = captures
+        .0
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .remove(&device_id);
+    let mut child = child.ok_or_else(|| format!("logcat of {device_id} is not being captured"))?;
+    let _ = child.kill();
+    let _ = child.wait();
+    Ok(())
+}
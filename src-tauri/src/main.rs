@@ -1,9 +1,329 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod logcat;
+
+use std::env::consts::EXE_SUFFIX;
+use std::ffi::{OsStr, OsString};
+use std::io::ErrorKind;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{PoisonError, RwLock};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{Manager, State};
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ToolPaths {
+    adb: Option<String>,
+    scrcpy: Option<String>,
+    /// `ADB` from the environment, scrcpy runs that adb when it is set
+    adb_env: Option<String>,
+    /// PATH the tools are looked up in, the frontend runs them with it too
+    search_path: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PingResult {
+    /// "reachable", "refused", "timeout", "unresolved" or "unreachable"
+    status: &'static str,
+    latency_ms: Option<u64>,
+    message: Option<String>,
+}
+
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+/// A profile that is slow or waits for input must not hold up startup.
+#[cfg(target_os = "macos")]
+const LOGIN_SHELL_TIMEOUT: Duration = Duration::from_secs(3);
+/// Anything bigger is not a list of scrcpy arguments.
+const ARGS_FILE_LIMIT: u64 = 64 * 1024;
+
+/// PATH adb and scrcpy are looked up in and run with, the process PATH until
+/// `refresh` found a better one. The process environment itself is never
+/// changed after startup, setting variables while other threads spawn
+/// processes is a data race.
+#[derive(Default)]
+struct SearchPath(RwLock<Option<OsString>>);
+
+impl SearchPath {
+    fn get(&self) -> OsString {
+        let path = self.0.read().unwrap_or_else(PoisonError::into_inner);
+        path.clone()
+            .or_else(|| std::env::var_os("PATH"))
+            .unwrap_or_default()
+    }
+
+    /// Merges the login shell's PATH in on macOS, a no-op elsewhere.
+    fn refresh(&self) {
+        #[cfg(target_os = "macos")]
+        if let Some(path) = login_shell_path() {
+            *self.0.write().unwrap_or_else(PoisonError::into_inner) = Some(path);
+        }
+    }
+}
+
+/// Apps started from Finder or the Dock only get launchd's minimal PATH, so
+/// adb/scrcpy installed through Homebrew can't be found. Returns the login
+/// shell's PATH followed by the remaining entries of the process PATH, or
+/// `None` when the shell didn't print it within LOGIN_SHELL_TIMEOUT.
+#[cfg(target_os = "macos")]
+fn login_shell_path() -> Option<OsString> {
+    use std::io::Read;
+    use std::process::Stdio;
+
+    const MARKER: &str = "__SCRCPY_GUI_PATH__";
+    let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/zsh".to_string());
+    let mut child = Command::new(shell)
+        .args([
+            "-l",
+            "-c",
+            &format!("printf '{MARKER}%s{MARKER}' \"$PATH\""),
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let mut pipe = child.stdout.take()?;
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let mut stdout = Vec::new();
+        let _ = pipe.read_to_end(&mut stdout);
+        let _ = sender.send(stdout);
+    });
+    let stdout = receiver.recv_timeout(LOGIN_SHELL_TIMEOUT);
+    // a no-op once the shell exited on its own
+    let _ = child.kill();
+    let _ = child.wait();
+    let stdout = stdout.ok()?;
+    // login scripts may print their own output around the marked PATH
+    let stdout = String::from_utf8_lossy(&stdout);
+    let login_path = stdout.split(MARKER).nth(1)?;
+    let mut paths: Vec<PathBuf> = std::env::split_paths(login_path).collect();
+    if let Some(current) = std::env::var_os("PATH") {
+        for path in std::env::split_paths(&current) {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+    std::env::join_paths(paths).ok()
+}
+
+fn find_in_path(name: &str, search_path: &OsStr) -> Option<PathBuf> {
+    let file_name = format!("{name}{EXE_SUFFIX}");
+    std::env::split_paths(search_path)
+        .map(|dir| dir.join(&file_name))
+        .find(|path| path.is_file())
+}
+
+fn hidden_command(program: &Path, search_path: &OsStr) -> Command {
+    let mut command = Command::new(program);
+    // adb starts its server from the PATH it was given
+    command.env("PATH", search_path);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        // CREATE_NO_WINDOW, avoids flashing a console in release builds
+        command.creation_flags(0x0800_0000);
+    }
+    command
+}
+
+#[tauri::command]
+fn is_file(path: String) -> bool {
+    Path::new(&path).is_file()
+}
+
+fn tool_paths(search_path: &OsStr) -> ToolPaths {
+    let resolve =
+        |name| find_in_path(name, search_path).map(|path| path.to_string_lossy().into_owned());
+    ToolPaths {
+        adb: resolve("adb"),
+        scrcpy: resolve("scrcpy"),
+        adb_env: std::env::var("ADB").ok().filter(|adb| !adb.is_empty()),
+        search_path: search_path.to_string_lossy().into_owned(),
+    }
+}
+
+/// Where adb and scrcpy resolve from PATH, `None` for a missing tool.
+#[tauri::command]
+fn get_tool_paths(search_path: State<SearchPath>) -> ToolPaths {
+    tool_paths(&search_path.get())
+}
+
+/// Resolves the tools again after the user fixed their install, on macOS the
+/// login shell's PATH is merged in again first. Emits `tool-paths-changed`.
+#[tauri::command]
+async fn refresh_tool_paths(app: tauri::AppHandle) -> Result<ToolPaths, String> {
+    // the login shell may take a while, keep it off the main thread
+    let handle = app.clone();
+    let paths = tauri::async_runtime::spawn_blocking(move || {
+        let search_path = handle.state::<SearchPath>();
+        search_path.refresh();
+        tool_paths(&search_path.get())
+    })
+    .await
+    .map_err(|error| error.to_string())?;
+    let _ = app.emit_all("tool-paths-changed", &paths);
+    Ok(paths)
+}
+
+/// Plain TCP connect to a wireless adb target, a much quicker answer than
+/// waiting for `adb connect` to give up.
+#[tauri::command]
+async fn ping_wireless_target(host: String, port: u16) -> Result<PingResult, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let addresses = match (host.as_str(), port).to_socket_addrs() {
+            Ok(addresses) => addresses.collect::<Vec<_>>(),
+            Err(error) => {
+                return PingResult {
+                    status: "unresolved",
+                    latency_ms: None,
+                    message: Some(error.to_string()),
+                }
+            }
+        };
+        let Some(address) = addresses.first() else {
+            return PingResult {
+                status: "unresolved",
+                latency_ms: None,
+                message: Some(format!("{host} has no address")),
+            };
+        };
+        let started = Instant::now();
+        match TcpStream::connect_timeout(address, PING_TIMEOUT) {
+            Ok(_) => PingResult {
+                status: "reachable",
+                latency_ms: Some(started.elapsed().as_millis() as u64),
+                message: None,
+            },
+            Err(error) => PingResult {
+                status: match error.kind() {
+                    ErrorKind::ConnectionRefused => "refused",
+                    ErrorKind::TimedOut | ErrorKind::WouldBlock => "timeout",
+                    _ => "unreachable",
+                },
+                latency_ms: None,
+                message: Some(error.to_string()),
+            },
+        }
+    })
+    .await
+    .map_err(|error| error.to_string())
+}
+
+/// Reads a user kept file of scrcpy arguments, the webview's fs scope doesn't
+/// cover arbitrary paths.
+#[tauri::command]
+fn read_args_file(path: String) -> Result<String, String> {
+    let metadata = std::fs::metadata(&path).map_err(|error| format!("{path}: {error}"))?;
+    if !metadata.is_file() {
+        return Err(format!("{path} is not a file"));
+    }
+    if metadata.len() > ARGS_FILE_LIMIT {
+        return Err(format!("{path} is too large for an arguments file"));
+    }
+    std::fs::read_to_string(&path).map_err(|error| format!("{path}: {error}"))
+}
+
+/// Opens the folder containing `path` in the file manager, with the file
+/// selected where the platform supports it.
+#[tauri::command]
+fn reveal_file(path: String) -> Result<(), String> {
+    let file = Path::new(&path);
+    if !file.is_file() {
+        return Err(format!("{path} does not exist"));
+    }
+    #[cfg(windows)]
+    let mut command = {
+        let mut command = Command::new("explorer");
+        // one "/select,C:\a b" argument would get quoted as a whole, which
+        // explorer doesn't understand
+        command.arg("/select,").arg(file);
+        command
+    };
+    #[cfg(target_os = "macos")]
+    let mut command = {
+        let mut command = Command::new("open");
+        command.arg("-R").arg(file);
+        command
+    };
+    #[cfg(not(any(windows, target_os = "macos")))]
+    let mut command = {
+        // xdg-open can't select a file, settle for its folder
+        let mut command = Command::new("xdg-open");
+        command.arg(file.parent().unwrap_or(Path::new(".")));
+        command
+    };
+    // explorer exits with 1 even when it opened the window, don't wait on it
+    command
+        .spawn()
+        .map(|_| ())
+        .map_err(|error| format!("failed to open the file manager: {error}"))
+}
+
+/// adb shipped in the same directory as the scrcpy found on PATH.
+#[tauri::command]
+fn bundled_adb_path(search_path: State<SearchPath>) -> Option<String> {
+    let adb = find_in_path("scrcpy", &search_path.get())?
+        .parent()?
+        .join(format!("adb{EXE_SUFFIX}"));
+    adb.is_file().then(|| adb.to_string_lossy().into_owned())
+}
+
+/// Reads the `Version x.y.z-build` line of `adb version`, using the adb on
+/// PATH when no path is given.
+#[tauri::command]
+async fn adb_version(
+    search_path: State<'_, SearchPath>,
+    path: Option<String>,
+) -> Result<String, String> {
+    let search_path = search_path.get();
+    tauri::async_runtime::spawn_blocking(move || {
+        let adb = match path {
+            Some(path) => PathBuf::from(path),
+            None => find_in_path("adb", &search_path).ok_or("adb was not found in PATH")?,
+        };
+        let output = hidden_command(&adb, &search_path)
+            .arg("version")
+            .output()
+            .map_err(|error| format!("failed to run {}: {error}", adb.display()))?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        stdout
+            .lines()
+            .find_map(|line| line.strip_prefix("Version "))
+            .map(|version| version.trim().to_string())
+            .ok_or_else(|| format!("unexpected adb version output: {stdout}"))
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
 
 fn main() {
+    // waits at most LOGIN_SHELL_TIMEOUT before the window shows up
+    let search_path = SearchPath::default();
+    search_path.refresh();
+
     tauri::Builder::default()
+        .manage(search_path)
+        .manage(logcat::LogcatCaptures::default())
+        .invoke_handler(tauri::generate_handler![
+            is_file,
+            get_tool_paths,
+            refresh_tool_paths,
+            bundled_adb_path,
+            adb_version,
+            ping_wireless_target,
+            reveal_file,
+            read_args_file,
+            logcat::start_logcat_to_file,
+            logcat::stop_logcat_to_file
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -0,0 +1,253 @@
+import { afterEach, beforeEach, describe, expect, it, vi } from "vitest";
+
+import {
+  AmbiguousDeviceError,
+  DeviceOfflineError,
+  adbError,
+  enableWireless,
+  getScrcpySessionId,
+  isDeviceOfflineLine,
+  isScrcpyRunning,
+  parseArgsFile,
+  parsePsOutput,
+  startScrcpy,
+  stopScrcpy,
+} from "./commands";
+import { windowTitleTemplate } from "./storage";
+import {
+  Command,
+  attachedDevices,
+  commands,
+  deviceProps,
+  execute,
+  spawnGate,
+} from "./test/tauri";
+
+const scrcpyCommands = (): Command[] => {
+  return commands.filter((command) => command.program.startsWith("scrcpy"));
+};
+const noop = (): void => undefined;
+// `adb devices -l` is cached for a moment, so it lists every serial the
+// launches below use. Each test has its own, a session that outlives a
+// failed test can't break the next one.
+const ATTACHED = [
+  "stop-before-spawn",
+  "stop-during-spawn",
+  "relaunched",
+  "titled",
+];
+beforeEach(() => {
+  attachedDevices.push(...ATTACHED);
+});
+
+describe("adbError", () => {
+  const output = (stderr: string) => ({
+    code: 1,
+    signal: null,
+    stdout: "",
+    stderr,
+  });
+  it("classifies more than one device as AmbiguousDeviceError", () => {
+    const error = adbError(
+      ["shell", "getprop"],
+      output("error: more than one device/emulator\n")
+    );
+    expect(error).toBeInstanceOf(AmbiguousDeviceError);
+  });
+  it("keeps any other message", () => {
+    const error = adbError(["shell", "getprop"], output("error: device offline"));
+    expect(error).not.toBeInstanceOf(AmbiguousDeviceError);
+    expect(error.message).toBe("error: device offline");
+  });
+  it("falls back to the exit code without output", () => {
+    const error = adbError(["reconnect"], output(""));
+    expect(error.message).toBe("adb reconnect exited with code 1");
+  });
+});
+
+describe("isDeviceOfflineLine", () => {
+  it.each([
+    "adb: device offline",
+    "ERROR: adb: device offline\n",
+    "error: device 'R3CN30XXXX' not found",
+  ])("matches %s", (line) => {
+    expect(isDeviceOfflineLine(line)).toBe(true);
+  });
+  it.each([
+    "INFO: Device: [samsung] samsung SM-G991B (Android 14)",
+    "ERROR: Server connection failed",
+  ])("ignores %s", (line) => {
+    expect(isDeviceOfflineLine(line)).toBe(false);
+  });
+});
+
+describe("stopScrcpy during a launch", () => {
+  it("cancels a launch that hasn't spawned yet", async () => {
+    const launch = startScrcpy(["-s", "stop-before-spawn"], noop, noop);
+    await stopScrcpy("stop-before-spawn");
+    await expect(launch).rejects.toThrow("was cancelled");
+    expect(scrcpyCommands()).toHaveLength(0);
+    expect(getScrcpySessionId("stop-before-spawn")).toBeUndefined();
+  });
+  it("kills a child that spawned after the stop", async () => {
+    let spawn = noop;
+    spawnGate.mockImplementationOnce(
+      () => new Promise<void>((resolve) => (spawn = resolve))
+    );
+    const onClose = vi.fn();
+    const launch = startScrcpy(["-s", "stop-during-spawn"], noop, onClose);
+    await vi.waitFor(() => expect(spawnGate).toHaveBeenCalled());
+    await stopScrcpy("stop-during-spawn");
+    spawn();
+    await expect(launch).rejects.toThrow("was cancelled");
+    const [scrcpy] = scrcpyCommands();
+    expect(scrcpy.child?.killed).toBe(true);
+    expect(isScrcpyRunning("stop-during-spawn")).toBe(false);
+    await vi.waitFor(() => expect(onClose).toHaveBeenCalled());
+    expect(getScrcpySessionId("stop-during-spawn")).toBeUndefined();
+  });
+});
+
+describe("startScrcpy device check", () => {
+  it("rejects a device adb doesn't list", async () => {
+    await expect(
+      startScrcpy(["-s", "not-attached"], noop, noop)
+    ).rejects.toBeInstanceOf(DeviceOfflineError);
+    expect(scrcpyCommands()).toHaveLength(0);
+  });
+  it("launches anyway with skipDeviceCheck", async () => {
+    await startScrcpy(["-s", "not-attached"], noop, noop, {
+      skipDeviceCheck: true,
+    });
+    const [scrcpy] = scrcpyCommands();
+    expect(scrcpy.args.slice(0, 2)).toEqual(["-s", "not-attached"]);
+    // the merged login shell PATH on macOS, see SearchPath in main.rs
+    expect(scrcpy.options.env?.PATH).toBe("/usr/bin");
+    scrcpy.close();
+  });
+});
+
+describe("scrcpy session ids", () => {
+  it("differ across a quick stop and relaunch", async () => {
+    const onLine = vi.fn();
+    const onClose = vi.fn();
+    await startScrcpy(["-s", "relaunched"], onLine, onClose);
+    const first = getScrcpySessionId("relaunched");
+    await stopScrcpy("relaunched");
+    await vi.waitFor(() => expect(onClose).toHaveBeenCalledTimes(1));
+    await startScrcpy(["-s", "relaunched"], onLine, onClose);
+    const second = getScrcpySessionId("relaunched");
+    expect(first).toBeDefined();
+    expect(second).not.toBe(first);
+    expect(onClose.mock.calls[0][0].sessionId).toBe(first);
+    // a late line of the old process still carries the old id
+    const [old, current] = scrcpyCommands();
+    old.stderr.emit("data", "late line\n");
+    expect(onLine).toHaveBeenCalledWith("late line\n", first);
+    current.close();
+  });
+});
+
+describe("window titles", () => {
+  afterEach(() => {
+    windowTitleTemplate.value = "";
+  });
+  const launchedArgs = async (args: string[]): Promise<string[]> => {
+    await startScrcpy(["-s", "titled", ...args], noop, noop);
+    const [scrcpy] = scrcpyCommands();
+    scrcpy.close();
+    return scrcpy.args;
+  };
+  it("passes a title with spaces as one argument", async () => {
+    const args = await launchedArgs(["--window-title=My Phone"]);
+    expect(args.filter((arg) => arg.includes("Phone"))).toEqual([
+      "--window-title=My Phone",
+    ]);
+  });
+  it("passes a title from the template as one argument", async () => {
+    windowTitleTemplate.value = "QA - {model}";
+    deviceProps["ro.product.model"] = "Pixel 7";
+    const args = await launchedArgs([]);
+    expect(args.filter((arg) => arg.includes("QA"))).toEqual([
+      "--window-title=QA - Pixel 7",
+    ]);
+  });
+});
+
+describe("parseArgsFile", () => {
+  it("keeps backslashes of unquoted Windows paths", () => {
+    expect(parseArgsFile("--record C:\\videos\\a.mp4")).toEqual([
+      "--record",
+      "C:\\videos\\a.mp4",
+    ]);
+  });
+  it("keeps backslashes of quoted Windows paths", () => {
+    expect(parseArgsFile('--record "C:\\new folder\\x.mp4"')).toEqual([
+      "--record",
+      "C:\\new folder\\x.mp4",
+    ]);
+  });
+  it("still escapes quotes and backslashes", () => {
+    expect(parseArgsFile('--window-title "say \\"hi\\" \\\\"')).toEqual([
+      "--window-title",
+      'say "hi" \\',
+    ]);
+  });
+});
+
+describe("parsePsOutput", () => {
+  it("reads toybox ps -o PID,NAME", () => {
+    const output = "  PID NAME\n    1 init\n  812 com.android.systemui\n";
+    expect(parsePsOutput(output)).toEqual([
+      { pid: 1, name: "init" },
+      { pid: 812, name: "com.android.systemui" },
+    ]);
+  });
+  it("skips the state column of toolbox ps", () => {
+    const output = [
+      "USER     PID   PPID  VSIZE  RSS     WCHAN    PC         NAME",
+      "root      1     0     8904   788   ffffffff 00000000 S /init",
+      "u0_a42    812   210   1024   512   ffffffff 00000000 S com.android.systemui",
+    ].join("\n");
+    expect(parsePsOutput(output)).toEqual([
+      { pid: 1, name: "/init" },
+      { pid: 812, name: "com.android.systemui" },
+    ]);
+  });
+});
+
+describe("enableWireless", () => {
+  const ipAddr = (lines: string[]) => (_program: string, args: string[]) => {
+    const shell = args.slice(3).join(" ");
+    return shell === "ip -f inet addr show"
+      ? { code: 0, stdout: lines.join("\n") }
+      : { code: 0 };
+  };
+  it("refuses a device on mobile data before adb tcpip", async () => {
+    execute.mockImplementation(
+      ipAddr(["    inet 10.64.12.7/30 scope global rmnet_data0"])
+    );
+    await expect(enableWireless("on-mobile-data")).rejects.toThrow(
+      "has no Wi-Fi address"
+    );
+    expect(commands.some((command) => command.args.includes("tcpip"))).toBe(
+      false
+    );
+  });
+  it("connects to the Wi-Fi address, not the mobile one", async () => {
+    execute.mockImplementation((program, args) =>
+      args[0] === "connect"
+        ? { code: 0, stdout: `connected to ${args[1]}\n` }
+        : ipAddr([
+            "    inet 10.64.12.7/30 scope global rmnet_data0",
+            "    inet 192.168.1.23/24 brd 192.168.1.255 scope global swlan0",
+          ])(program, args)
+    );
+    // adbd gets a second to restart before the first connect
+    vi.useFakeTimers();
+    const serial = enableWireless("on-wifi");
+    await vi.advanceTimersByTimeAsync(1000);
+    vi.useRealTimers();
+    await expect(serial).resolves.toBe("192.168.1.23:5555");
+  });
+});
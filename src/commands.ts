@@ -1,5 +1,23 @@
-import { Command, Child } from "@tauri-apps/api/shell";
-import { type } from "@tauri-apps/api/os";
+import { Command, Child, ChildProcess } from "@tauri-apps/api/shell";
+import { arch, type, version } from "@tauri-apps/api/os";
+import { writeTextFile } from "@tauri-apps/api/fs";
+import { invoke } from "@tauri-apps/api/tauri";
+import { listen, UnlistenFn } from "@tauri-apps/api/event";
+import { fetch } from "@tauri-apps/api/http";
+import { availableMonitors } from "@tauri-apps/api/window";
+import { writeText } from "@tauri-apps/api/clipboard";
+
+import { isEmulatorSerial, isWirelessSerial } from "./serial";
+import {
+  collapseTransports,
+  getDeviceMetadata,
+  getPreset,
+  preferBundledAdb,
+  preferSystemAdb,
+  scrcpyServerPath,
+  scrcpyVerbosity,
+  windowTitleTemplate,
+} from "./storage";
 
 let _binaryExtension = ".exe";
 export async function binaryExtension() {
@@ -8,13 +26,248 @@ export async function binaryExtension() {
   }
 }
 
+// PATH the backend resolved, on macOS with the login shell's merged in. The
+// webview's environment is never updated, so every adb and scrcpy run gets
+// it passed explicitly.
+let searchPath: Promise<string | undefined> | undefined;
+const toolEnv = async (
+  env: Record<string, string> = {}
+): Promise<Record<string, string>> => {
+  if (!searchPath) {
+    searchPath = getToolPaths().then(
+      (paths) => paths.searchPath,
+      () => {
+        // try again next time, the tools run with the inherited PATH meanwhile
+        searchPath = undefined;
+        return undefined;
+      }
+    );
+  }
+  const path = await searchPath;
+  return path ? { PATH: path, ...env } : env;
+};
+const adbCommand = async (args: string[]): Promise<Command> => {
+  return new Command("adb" + _binaryExtension, args, { env: await toolEnv() });
+};
+
 export const executeAdbDevices = (callback: (line: string) => void): void => {
-  const command = new Command("adb" + _binaryExtension, ["devices"]);
-  command.on("error", callback);
-  command.stdout.on("data", callback);
-  command.stderr.on("data", callback);
+  adbCommand(["devices"]).then((command) => {
+    command.on("error", callback);
+    command.stdout.on("data", callback);
+    command.stderr.on("data", callback);
+
+    command.spawn();
+  });
+};
+const executeAdb = async (args: string[]): Promise<ChildProcess> => {
+  return (await adbCommand(args)).execute();
+};
+
+// adb needs -s <serial> as soon as more than one device is attached
+export class AmbiguousDeviceError extends Error {
+  constructor() {
+    super("More than one device is connected, select a specific device");
+    this.name = "AmbiguousDeviceError";
+  }
+}
+export const adbError = (args: string[], output: ChildProcess): Error => {
+  const message = (output.stderr || output.stdout).trim();
+  if (/more than one (device|emulator)/i.test(message)) {
+    return new AmbiguousDeviceError();
+  }
+  return new Error(
+    message || `adb ${args.join(" ")} exited with code ${output.code}`
+  );
+};
+// rejects with adb's own message when it exits with a non-zero code
+const executeAdbChecked = async (args: string[]): Promise<ChildProcess> => {
+  const output = await executeAdb(args);
+  if (output.code !== 0) {
+    throw adbError(args, output);
+  }
+  return output;
+};
+
+const delay = (milliseconds: number): Promise<void> => {
+  return new Promise((resolve) => setTimeout(resolve, milliseconds));
+};
+
+// errors printed while the adb daemon restarts, worth a retry; a missing adb
+// binary rejects in executeAdb and is never retried
+const TRANSIENT_ADB_ERROR =
+  /protocol fault|daemon not running|cannot connect to daemon|connection reset|failed to start daemon/i;
+const ADB_RETRY_DELAYS = [250, 500, 1000];
+const executeAdbWithRetry = async (args: string[]): Promise<ChildProcess> => {
+  for (let attempt = 0; ; attempt++) {
+    const output = await executeAdb(args);
+    if (
+      output.code === 0 ||
+      attempt >= ADB_RETRY_DELAYS.length ||
+      !TRANSIENT_ADB_ERROR.test(output.stderr + output.stdout)
+    ) {
+      if (output.code !== 0) {
+        throw adbError(args, output);
+      }
+      return output;
+    }
+    await delay(ADB_RETRY_DELAYS[attempt]);
+  }
+};
+
+// each output stream of runAdbShell is cut to this many characters
+const ADB_SHELL_OUTPUT_LIMIT = 64 * 1024;
+export type AdbShellResult = {
+  stdout: string;
+  stderr: string;
+  exitCode: number | null;
+};
+// Runs a short command on the device and resolves once it exits, it is not
+// meant for streaming commands like logcat. Every element of `command` is
+// passed to adb as its own argument, nothing goes through a local shell.
+export const runAdbShell = async (
+  deviceId: string,
+  command: string[]
+): Promise<AdbShellResult> => {
+  if (!command.length) {
+    throw new Error("Shell command is empty");
+  }
+  const output = await executeAdb(["-s", deviceId, "shell", ...command]);
+  return {
+    stdout: output.stdout.slice(0, ADB_SHELL_OUTPUT_LIMIT),
+    stderr: output.stderr.slice(0, ADB_SHELL_OUTPUT_LIMIT),
+    exitCode: output.code,
+  };
+};
+
+// running shell streams by the id startAdbShellStream resolved with
+const shellStreams = new Map<number, Child>();
+let nextShellStreamId = 1;
+// The streaming counterpart of runAdbShell for commands that run until
+// stopped, like `top -b` or `getevent`. Output is handed over as it arrives,
+// onClose runs once the command exits or is stopped.
+export const startAdbShellStream = async (
+  deviceId: string,
+  command: string[],
+  onLine: (line: string, stream: "stdout" | "stderr") => void,
+  onClose?: (code: number | null) => void
+): Promise<number> => {
+  if (!command.length) {
+    throw new Error("Shell command is empty");
+  }
+  const id = nextShellStreamId++;
+  const shell = await adbCommand([
+    "-s",
+    deviceId,
+    "shell",
+    ...command,
+  ]);
+  shell.stdout.on("data", (line) => onLine(line, "stdout"));
+  shell.stderr.on("data", (line) => onLine(line, "stderr"));
+  shell.on("error", (error) => onLine(`${error}\n`, "stderr"));
+  let closed = false;
+  shell.on("close", ({ code }) => {
+    closed = true;
+    shellStreams.delete(id);
+    onClose?.(code);
+  });
+  const child = await shell.spawn();
+  // a command that failed right away may have closed before spawn resolved
+  if (!closed) {
+    shellStreams.set(id, child);
+  }
+  return id;
+};
+export const stopAdbShellStream = async (id: number): Promise<void> => {
+  const child = shellStreams.get(id);
+  shellStreams.delete(id);
+  await child?.kill();
+};
+
+// adb joins shell arguments with spaces and the device shell splits them
+// again, quote values that have to stay a single argument over there
+export const quoteDeviceShellArg = (value: string): string => {
+  return `'${value.replace(/'/g, `'\\''`)}'`;
+};
 
-  command.spawn();
+// undefined when the dumpsys output format is not recognized
+export const getDeviceScreenOn = async (
+  deviceId: string
+): Promise<boolean | undefined> => {
+  const { stdout } = await runAdbShell(deviceId, ["dumpsys", "power"]);
+  const [, wakefulness] = stdout.match(/mWakefulness=(\w+)/) || [];
+  if (wakefulness) {
+    return wakefulness === "Awake";
+  }
+  const [, screenOn] = stdout.match(/mScreenOn=(true|false)/) || [];
+  if (screenOn) {
+    return screenOn === "true";
+  }
+  const [, displayState] = stdout.match(/Display Power: state=(\w+)/) || [];
+  return displayState ? displayState === "ON" : undefined;
+};
+// "showing=true" under KeyguardServiceDelegate on recent releases,
+// mShowingLockscreen / mDreamingLockscreen on older ones
+const KEYGUARD_PATTERNS = [
+  /KeyguardServiceDelegate[\s\S]*?\bshowing=(true|false)/,
+  /mShowingLockscreen=(true|false)/,
+  /mDreamingLockscreen=(true|false)/,
+];
+// undefined when the dumpsys output format is not recognized, a device with
+// its screen off counts as locked
+export const isDeviceLocked = async (
+  deviceId: string
+): Promise<boolean | undefined> => {
+  if ((await getDeviceScreenOn(deviceId)) === false) {
+    return true;
+  }
+  const { stdout } = await runAdbShell(deviceId, [
+    "dumpsys",
+    "window",
+    "policy",
+  ]);
+  for (const pattern of KEYGUARD_PATTERNS) {
+    const [, showing] = stdout.match(pattern) || [];
+    if (showing) {
+      return showing === "true";
+    }
+  }
+  return undefined;
+};
+// Wakes or sleeps the physical device without touching a running scrcpy
+// session. This is unrelated to scrcpy's --turn-screen-off, which keeps the
+// device awake and only powers the panel down, a sleeping device usually
+// mirrors a black screen.
+export const setDeviceScreen = async (
+  deviceId: string,
+  on: boolean
+): Promise<boolean | undefined> => {
+  if ((await getDeviceScreenOn(deviceId)) !== on) {
+    await runAdbShell(deviceId, [
+      "input",
+      "keyevent",
+      on ? "KEYCODE_WAKEUP" : "KEYCODE_SLEEP",
+    ]);
+  }
+  return getDeviceScreenOn(deviceId);
+};
+
+// Wakes an asleep device before mirroring and optionally dismisses a lock
+// screen without security. Unlike scrcpy's --stay-awake this is a one-off
+// action that doesn't depend on a running session or a USB connection.
+export const wakeDevice = async (
+  deviceId: string,
+  dismissKeyguard = false
+): Promise<boolean | undefined> => {
+  await runAdbShell(deviceId, ["input", "keyevent", "KEYCODE_WAKEUP"]);
+  if (dismissKeyguard) {
+    await runAdbShell(deviceId, ["wm", "dismiss-keyguard"]);
+  }
+  return getDeviceScreenOn(deviceId);
+};
+
+const parseDeviceLine = (line: string): string | undefined => {
+  const [, deviceId] = line.trim().match(/(.*)\s+device$/) || [];
+  return deviceId;
 };
 export const getDevices = (
   callback: (line: string) => void,
@@ -22,22 +275,2371 @@ export const getDevices = (
 ): void => {
   executeAdbDevices((line) => {
     log?.(line);
-    const [, deviceId] = line.trim().match(/(.*)\s+device$/) || [];
+    const deviceId = parseDeviceLine(line);
     if (deviceId) {
       callback(deviceId);
     }
   });
 };
+
+export type DeviceInfo = {
+  serial: string;
+  // epoch milliseconds of the first listing that contained this device
+  connectedSince: number;
+  alias?: string;
+  color?: string;
+  tag?: string;
+  isWireless: boolean;
+  isEmulator: boolean;
+  // fields reported by `adb devices -l`, any of them may be missing
+  product?: string;
+  model?: string;
+  device?: string;
+  transportId?: string;
+  // other serials of the same phone, only set when transports are collapsed
+  otherTransports?: string[];
+};
+type DeviceListEntry = Pick<
+  DeviceInfo,
+  "serial" | "product" | "model" | "device" | "transportId"
+>;
+// parses a ready device of `adb devices -l`, e.g.
+// "R3CN30XXXX device usb:1-1 product:panther model:Pixel_7 transport_id:2"
+const parseDeviceListLine = (line: string): DeviceListEntry | undefined => {
+  const [serial, state, ...tokens] = line.trim().split(/\s+/);
+  if (!serial || state !== "device") {
+    return undefined;
+  }
+  const fields = new Map(
+    tokens
+      .filter((token) => token.includes(":"))
+      .map((token): [string, string] => {
+        const separator = token.indexOf(":");
+        return [token.slice(0, separator), token.slice(separator + 1)];
+      })
+  );
+  return {
+    serial,
+    product: fields.get("product"),
+    model: fields.get("model")?.replace(/_/g, " "),
+    device: fields.get("device"),
+    transportId: fields.get("transport_id"),
+  };
+};
+// serial -> connectedSince, only holds devices seen in the latest listing
+const connectedSince = new Map<string, number>();
+const trackConnectedDevices = (serials: string[]): void => {
+  for (const serial of connectedSince.keys()) {
+    if (!serials.includes(serial)) {
+      connectedSince.delete(serial);
+      scrcpyExitHistory.delete(serial);
+      hardwareSerials.delete(serial);
+      deviceModels.delete(serial);
+    }
+  }
+  const now = Date.now();
+  for (const serial of serials) {
+    if (!connectedSince.has(serial)) {
+      connectedSince.set(serial, now);
+    }
+  }
+};
+// Overlapping listings share a single `adb devices` process and a settled
+// result is reused for a short while, so rapid refreshes don't thrash the
+// adb server. Failures are never cached.
+const DEVICE_LIST_CACHE_TTL = 500;
+type SharedDeviceList = { output: Promise<ChildProcess>; settledAt?: number };
+let sharedDeviceList: SharedDeviceList | undefined;
+const executeSharedAdbDevices = (): Promise<ChildProcess> => {
+  if (
+    sharedDeviceList &&
+    (sharedDeviceList.settledAt === undefined ||
+      Date.now() - sharedDeviceList.settledAt < DEVICE_LIST_CACHE_TTL)
+  ) {
+    return sharedDeviceList.output;
+  }
+  const current: SharedDeviceList = {
+    output: executeAdbWithRetry(["devices", "-l"]),
+  };
+  current.output.then(
+    () => {
+      current.settledAt = Date.now();
+    },
+    () => {
+      if (sharedDeviceList === current) {
+        sharedDeviceList = undefined;
+      }
+    }
+  );
+  sharedDeviceList = current;
+  return current.output;
+};
+// Drops the cached listing and tells listeners, such as the device monitor,
+// that the next one would differ, e.g. after an `adb connect`
+const deviceListListeners = new Set<() => void>();
+export const onDeviceListInvalidated = (listener: () => void): (() => void) => {
+  deviceListListeners.add(listener);
+  return () => deviceListListeners.delete(listener);
+};
+const invalidateDeviceList = (): void => {
+  sharedDeviceList = undefined;
+  deviceListListeners.forEach((listener) => listener());
+};
+export const listDevices = async (
+  log?: (line: string) => void
+): Promise<DeviceInfo[]> => {
+  const output = await executeSharedAdbDevices();
+  log?.(output.stdout);
+  log?.(output.stderr);
+  const entries = output.stdout
+    .split("\n")
+    .map(parseDeviceListLine)
+    .filter((entry): entry is DeviceListEntry => !!entry)
+    .filter(
+      (entry, index, all) =>
+        all.findIndex((other) => other.serial === entry.serial) === index
+    );
+  trackConnectedDevices(entries.map((entry) => entry.serial));
+  return entries.map((entry) => ({
+    ...entry,
+    isWireless: isWirelessSerial(entry.serial),
+    isEmulator: isEmulatorSerial(entry.serial),
+    connectedSince: connectedSince.get(entry.serial) as number,
+    ...getDeviceMetadata(entry.serial),
+  }));
+};
+// adb serial -> ro.serialno, a wireless serial says nothing about the phone
+const hardwareSerials = new Map<string, string>();
+const getHardwareSerial = async (serial: string): Promise<string> => {
+  let hardwareSerial = hardwareSerials.get(serial);
+  if (hardwareSerial === undefined) {
+    const { stdout } = await runAdbShell(serial, ["getprop", "ro.serialno"]);
+    // emulators and some builds report nothing, keep those apart
+    hardwareSerial = stdout.trim() || serial;
+    hardwareSerials.set(serial, hardwareSerial);
+  }
+  return hardwareSerial;
+};
+// Merges the entries that belong to one phone. The USB entry is kept when
+// there is one, the other serials end up in its otherTransports.
+export const collapseDeviceTransports = async (
+  devices: DeviceInfo[]
+): Promise<DeviceInfo[]> => {
+  const keys = await Promise.all(
+    devices.map((device) =>
+      getHardwareSerial(device.serial).catch(() => device.serial)
+    )
+  );
+  const groups = new Map<string, DeviceInfo[]>();
+  devices.forEach((device, index) => {
+    groups.set(keys[index], [...(groups.get(keys[index]) || []), device]);
+  });
+  return [...groups.values()].map((group) => {
+    if (group.length === 1) {
+      return group[0];
+    }
+    const primary = group.find((device) => !device.isWireless) || group[0];
+    return {
+      ...primary,
+      otherTransports: group
+        .filter((device) => device !== primary)
+        .map((device) => device.serial),
+    };
+  });
+};
+// listDevices, collapsed per phone when the user opted in
+export const listVisibleDevices = async (
+  log?: (line: string) => void
+): Promise<DeviceInfo[]> => {
+  const devices = await listDevices(log);
+  return collapseTransports.value
+    ? collapseDeviceTransports(devices)
+    : devices;
+};
+export const isFile = (path: string): Promise<boolean> => {
+  return invoke<boolean>("is_file", { path });
+};
+export type ToolPaths = {
+  adb: string | null;
+  scrcpy: string | null;
+  // the ADB environment variable scrcpy inherits, if set
+  adbEnv: string | null;
+  // PATH both are looked up in
+  searchPath: string;
+};
+export const getToolPaths = (): Promise<ToolPaths> => {
+  return invoke<ToolPaths>("get_tool_paths");
+};
+// re-resolves from PATH after the user fixed their install, without a restart
+export const refreshToolPaths = (): Promise<ToolPaths> => {
+  return invoke<ToolPaths>("refresh_tool_paths");
+};
+export const onToolPathsChanged = (
+  handler: (paths: ToolPaths) => void
+): Promise<UnlistenFn> => {
+  return listen<ToolPaths>("tool-paths-changed", (event) =>
+    handler(event.payload)
+  );
+};
+onToolPathsChanged((paths) => {
+  searchPath = Promise.resolve(paths.searchPath);
+});
+// single first-run check, rejects with the names of the missing tools
+export const ensureTools = async (): Promise<ToolPaths> => {
+  const paths = await getToolPaths();
+  const missing = (["adb", "scrcpy"] as const).filter((tool) => !paths[tool]);
+  if (missing.length) {
+    throw new Error(
+      `${missing.join(" and ")} not found in PATH, install scrcpy and make sure its directory is in PATH`
+    );
+  }
+  return paths;
+};
+// an empty or missing path goes back to scrcpy's own lookup
+export const setScrcpyServerPath = async (
+  path?: string | null
+): Promise<void> => {
+  const serverPath = path?.trim() || "";
+  if (serverPath && !(await isFile(serverPath))) {
+    throw new Error(`scrcpy server not found: ${serverPath}`);
+  }
+  scrcpyServerPath.value = serverPath;
+};
+export const getBundledAdbPath = (): Promise<string | null> => {
+  return invoke<string | null>("bundled_adb_path");
+};
+
+// "manual" is a per launch override, "bundled" the adb next to scrcpy
+export type ToolSource = "env" | "path" | "bundled" | "manual" | "none";
+export type ResolvedTool = { path: string | null; source: ToolSource };
+// The adb scrcpy ends up using for a launch and why, in the order
+// startScrcpy applies: override, preferred adb, ADB variable, then scrcpy's
+// own lookup of "adb", which on Windows finds the one next to scrcpy.exe.
+export const resolveScrcpyAdb = async (
+  adbPath?: string
+): Promise<ResolvedTool> => {
+  const manual = adbPath?.trim();
+  if (manual) {
+    return { path: manual, source: "manual" };
+  }
+  const paths = await getToolPaths();
+  const bundled =
+    preferBundledAdb.value || (!preferSystemAdb.value && !paths.adbEnv)
+      ? await getBundledAdbPath()
+      : null;
+  if (preferBundledAdb.value && bundled) {
+    return { path: bundled, source: "bundled" };
+  }
+  if (preferSystemAdb.value && paths.adb) {
+    return { path: paths.adb, source: "path" };
+  }
+  if (paths.adbEnv) {
+    return { path: paths.adbEnv, source: "env" };
+  }
+  if (bundled && (await type()) === "Windows_NT") {
+    return { path: bundled, source: "bundled" };
+  }
+  return paths.adb
+    ? { path: paths.adb, source: "path" }
+    : { path: null, source: "none" };
+};
+export type DetailedToolPaths = { adb: ResolvedTool; scrcpy: ResolvedTool };
+export const getToolPathsDetailed = async (
+  adbPath?: string
+): Promise<DetailedToolPaths> => {
+  const [adb, { scrcpy }] = await Promise.all([
+    resolveScrcpyAdb(adbPath),
+    getToolPaths(),
+  ]);
+  return {
+    adb,
+    scrcpy: scrcpy
+      ? { path: scrcpy, source: "path" }
+      : { path: null, source: "none" },
+  };
+};
+// Environment of every scrcpy run that talks to a device, so the adb and
+// server it uses are the same as for a launch
+const scrcpyEnv = async (
+  adb?: ResolvedTool
+): Promise<Record<string, string>> => {
+  const env = await toolEnv();
+  adb = adb || (await resolveScrcpyAdb());
+  // an inherited ADB or scrcpy's own lookup need no override
+  const overridden =
+    adb.source === "manual" ||
+    adb.source === "bundled" ||
+    (adb.source === "path" && preferSystemAdb.value);
+  if (overridden && adb.path) {
+    if (!(await isFile(adb.path))) {
+      throw new Error(`ADB binary not found: ${adb.path}`);
+    }
+    env.ADB = adb.path;
+  }
+  if (scrcpyServerPath.value) {
+    env.SCRCPY_SERVER_PATH = scrcpyServerPath.value;
+  }
+  return env;
+};
+// adb on PATH when `path` is omitted
+export const getAdbVersion = (path?: string): Promise<string> => {
+  return invoke<string>("adb_version", { path });
+};
+// scrcpy's server expects the adb it ships with, warn when the adb in use
+// has a different major version, e.g. 33 vs 34 of "34.0.4-10411341"
+export const checkAdbCompatibility = async (
+  log: (line: string) => void,
+  adbPath?: string
+): Promise<void> => {
+  const bundledAdbPath = await getBundledAdbPath();
+  if (!bundledAdbPath) {
+    return;
+  }
+  const [usedVersion, bundledVersion] = await Promise.all([
+    getAdbVersion(adbPath),
+    getAdbVersion(bundledAdbPath),
+  ]);
+  if (usedVersion.split(".")[0] !== bundledVersion.split(".")[0]) {
+    log(
+      `Warning: adb ${usedVersion} differs from adb ${bundledVersion} bundled with scrcpy at ${bundledAdbPath}, enable "Prefer bundled adb" if scrcpy fails to connect to its server\n`
+    );
+  }
+};
+
+const hasFlag = (args: string[], flag: string): boolean => {
+  return args.some((arg) => arg === flag || arg.startsWith(flag + "="));
+};
+const flagValue = (args: string[], flag: string): string | undefined => {
+  const index = args.findIndex(
+    (arg) => arg === flag || arg.startsWith(flag + "=")
+  );
+  if (index === -1) {
+    return undefined;
+  }
+  return args[index] === flag ? args[index + 1] : args[index].split("=")[1];
+};
+
+// Known incompatible scrcpy flag combinations. A rule applies when `flag` is
+// present (with `value` if given), it then forbids every flag in `conflicts`
+// and needs at least one of `requires`.
+type ScrcpyArgRule = {
+  flag: string;
+  value?: string;
+  conflicts?: string[];
+  requires?: string[];
+};
+const scrcpyArgRules: ScrcpyArgRule[] = [
+  {
+    // OTG mode only forwards keyboard and mouse, there is no video at all
+    flag: "--otg",
+    conflicts: [
+      "--video-source",
+      "--max-size",
+      "--max-fps",
+      "--video-codec",
+      "--record",
+      "--turn-screen-off",
+      "--show-touches",
+      "--stay-awake",
+    ],
+  },
+  {
+    flag: "--video-source",
+    value: "camera",
+    requires: ["--camera-id", "--camera-facing"],
+  },
+];
+// returns a readable error for the first violated rule, undefined if valid
+export const validateScrcpyArgs = (args: string[]): string | undefined => {
+  for (const rule of scrcpyArgRules) {
+    if (!hasFlag(args, rule.flag)) {
+      continue;
+    }
+    if (rule.value !== undefined && flagValue(args, rule.flag) !== rule.value) {
+      continue;
+    }
+    const name = rule.value ? `${rule.flag}=${rule.value}` : rule.flag;
+    const conflict = rule.conflicts?.find((flag) => hasFlag(args, flag));
+    if (conflict) {
+      return `${name} cannot be used together with ${conflict}`;
+    }
+    if (rule.requires && !rule.requires.some((flag) => hasFlag(args, flag))) {
+      return `${name} requires ${rule.requires.join(" or ")}`;
+    }
+  }
+  return undefined;
+};
+
+// scrcpy output of the latest session per device, kept until the next launch
+const SCRCPY_LOG_LIMIT = 500;
+const scrcpyLogs = new Map<string, string[]>();
+const appendScrcpyLog = (deviceId: string, line: string): void => {
+  const logs = scrcpyLogs.get(deviceId);
+  if (!logs) {
+    return;
+  }
+  logs.push(line);
+  if (logs.length > SCRCPY_LOG_LIMIT) {
+    logs.splice(0, logs.length - SCRCPY_LOG_LIMIT);
+  }
+};
+export const getScrcpyLogs = (deviceId: string): string[] => {
+  return [...(scrcpyLogs.get(deviceId) || [])];
+};
+
+// the latest exits per device, oldest first, dropped when it disconnects
+const SCRCPY_EXIT_HISTORY_LIMIT = 10;
+export type ScrcpyExitRecord = {
+  code: number;
+  signal: string;
+  // epoch milliseconds
+  exitedAt: number;
+  lastStderr?: string;
+};
+const scrcpyExitHistory = new Map<string, ScrcpyExitRecord[]>();
+export const getScrcpyExitHistory = (deviceId: string): ScrcpyExitRecord[] => {
+  return [...(scrcpyExitHistory.get(deviceId) || [])];
+};
+const recordScrcpyExit = (
+  deviceId: string,
+  record: ScrcpyExitRecord
+): void => {
+  const history = scrcpyExitHistory.get(deviceId) || [];
+  history.push(record);
+  scrcpyExitHistory.set(deviceId, history.slice(-SCRCPY_EXIT_HISTORY_LIMIT));
+};
+
+// scrcpy output that deserves a plain explanation in the log
+const scrcpyHints: { pattern: RegExp; hint: string }[] = [
+  {
+    pattern: /audio.*(not supported before|requires) Android 11/i,
+    hint: "Audio forwarding needs Android 11 or newer, the device is mirrored without sound",
+  },
+];
+export const scrcpyHint = (line: string): string | undefined => {
+  return scrcpyHints.find(({ pattern }) => pattern.test(line))?.hint;
+};
+
+// sessions launched by startScrcpy, keyed by device serial
+type ScrcpySession = {
+  // increases with every launch, lets listeners drop events of old sessions
+  id: number;
+  state: "starting" | "running" | "stopRequested";
+  child?: Child;
+  // what scrcpy was spawned with, including the flags startScrcpy added
+  launchArgs?: string[];
+  // settles once the process exited, or the launch gave up before spawning
+  ended: Promise<void>;
+};
+const scrcpySessions = new Map<string, ScrcpySession>();
+let nextScrcpySessionId = 1;
+export const getScrcpySessionId = (deviceId: string): number | undefined => {
+  return scrcpySessions.get(deviceId)?.id;
+};
+export const getScrcpyLaunchArgs = (
+  deviceId: string
+): string[] | undefined => {
+  return scrcpySessions.get(deviceId)?.launchArgs;
+};
+export type ScrcpyExit = {
+  code: number;
+  signal: string;
+  sessionId: number;
+  // output line of a failed start that is usually fixed by trying again
+  retryReason?: string;
+  // set when scrcpy reported the device offline or gone, e.g. when it
+  // dropped between the device check and the spawn
+  offline?: DeviceOfflineError;
+};
+// exits within this window after spawning count as a failed start
+const EARLY_EXIT_WINDOW = 5000;
+const TRANSIENT_SCRCPY_ERROR =
+  /Could not find any ADB device|Server connection failed|Could not connect to the server|adb server (is out of date|didn't ACK)/i;
+// "adb: device offline", "error: device 'R3CN30XXXX' not found"
+const DEVICE_OFFLINE_ERROR = /\bdevice offline\b|\bdevice '[^']*' not found/i;
+export const isDeviceOfflineLine = (line: string): boolean => {
+  return DEVICE_OFFLINE_ERROR.test(line);
+};
+// true once the process has spawned, false while it is still starting
+export const isScrcpyRunning = (deviceId: string): boolean => {
+  return scrcpySessions.get(deviceId)?.state === "running";
+};
+// Kills a running session, or makes a launch that is still starting abort
+// and reject instead of leaving an untracked scrcpy behind.
+export const stopScrcpy = async (deviceId: string): Promise<void> => {
+  const session = scrcpySessions.get(deviceId);
+  if (!session || session.state === "stopRequested") {
+    return;
+  }
+  const child = session.state === "running" ? session.child : undefined;
+  session.state = "stopRequested";
+  await child?.kill();
+};
+export const stopAllScrcpy = async (): Promise<void> => {
+  await Promise.all([...scrcpySessions.keys()].map(stopScrcpy));
+};
+
+// serial -> ro.product.model, read once per connection for window titles
+const deviceModels = new Map<string, string>();
+const getDeviceModel = async (deviceId: string): Promise<string> => {
+  let model = deviceModels.get(deviceId);
+  if (model === undefined) {
+    const { stdout } = await runAdbShell(deviceId, [
+      "getprop",
+      "ro.product.model",
+    ]);
+    model = stdout.trim();
+    deviceModels.set(deviceId, model);
+  }
+  return model;
+};
+// {alias} falls back to the model and {model} to the serial so a template
+// never produces an empty title
+const resolveWindowTitle = async (
+  template: string,
+  deviceId: string
+): Promise<string> => {
+  const alias = getDeviceMetadata(deviceId).alias;
+  const needsModel =
+    template.includes("{model}") || (template.includes("{alias}") && !alias);
+  const model = needsModel
+    ? (await getDeviceModel(deviceId).catch(() => "")) || deviceId
+    : deviceId;
+  const values: Record<string, string> = {
+    serial: deviceId,
+    model,
+    alias: alias || model,
+  };
+  return template.replace(
+    /\{(serial|alias|model)\}/g,
+    (_, name: string) => values[name]
+  );
+};
+
+// Stops the device's session, if any, waits until its process is gone and
+// launches again with `args`, e.g. after changing settings. Without a
+// session this is a plain startScrcpy.
+export const restartScrcpy = async (
+  args: string[],
+  callback: (line: string, sessionId: number) => void,
+  onClose: (data: ScrcpyExit) => void,
+  options: StartScrcpyOptions = {}
+): Promise<Child> => {
+  const deviceId = flagValue(args, "-s") || flagValue(args, "--serial");
+  const session = deviceId ? scrcpySessions.get(deviceId) : undefined;
+  if (deviceId && session) {
+    await stopScrcpy(deviceId);
+    await session.ended;
+    // another launch may have claimed the device while this one waited
+    if (scrcpySessions.has(deviceId)) {
+      throw new Error(`scrcpy for ${deviceId} was started again meanwhile`);
+    }
+  }
+  return startScrcpy(args, callback, onClose, options);
+};
+
+export class DeviceOfflineError extends Error {
+  constructor(deviceId: string) {
+    super(`Device ${deviceId} is not connected or not authorized`);
+    this.name = "DeviceOfflineError";
+  }
+}
+
+export type StartScrcpyOptions = {
+  // adb binary used for this launch only, scrcpy reads it from the ADB env var
+  adbPath?: string;
+  // launch even if the device isn't listed by adb, e.g. with --tcpip=<ip>
+  skipDeviceCheck?: boolean;
+  // hold the launch until the user unlocked the device, then start mirroring
+  waitForUnlock?: boolean;
+  // rejects the launch when the device stays locked this long, default 60s
+  unlockTimeoutMs?: number;
+  // appends the args of this saved preset, see savePreset
+  presetName?: string;
+};
+// args of a preset for a launch, checking the device it is bound to
+const presetArgs = (args: string[], presetName: string): string[] => {
+  const preset = getPreset(presetName);
+  if (!preset) {
+    throw new Error(`No preset named ${presetName}`);
+  }
+  const deviceId = flagValue(args, "-s") || flagValue(args, "--serial");
+  if (preset.deviceId && preset.deviceId !== deviceId) {
+    throw new Error(
+      `Preset ${preset.name} is bound to ${preset.deviceId}, not ${deviceId}`
+    );
+  }
+  return preset.args;
+};
+const UNLOCK_POLL_INTERVAL = 1000;
+const UNLOCK_TIMEOUT = 60000;
+// Every element of `args` reaches scrcpy as exactly one argument: no shell is
+// involved and on Windows the Rust side quotes each element following the
+// CommandLineToArgvW rules. Pass ["--window-title=My Phone"] or
+// ["--window-title", "My Phone"], never add quotes around values yourself.
 export const startScrcpy = async (
   args: string[],
-  callback: (line: string) => void,
-  onClose: (data: {code: number, signal: string}) => void
+  callback: (line: string, sessionId: number) => void,
+  onClose: (data: ScrcpyExit) => void,
+  options: StartScrcpyOptions = {}
+): Promise<Child> => {
+  if (options.presetName !== undefined) {
+    args = [...args, ...presetArgs(args, options.presetName)];
+  }
+  const invalidArgs = validateScrcpyArgs(args);
+  if (invalidArgs) {
+    throw new Error(invalidArgs);
+  }
+  const deviceId = flagValue(args, "-s") || flagValue(args, "--serial");
+  // a second launch would leave the first scrcpy untracked and unstoppable
+  if (deviceId && scrcpySessions.has(deviceId)) {
+    throw new Error(`scrcpy for ${deviceId} is already started`);
+  }
+  let resolveEnded = (): void => undefined;
+  const session: ScrcpySession = {
+    id: nextScrcpySessionId++,
+    state: "starting",
+    ended: new Promise<void>((resolve) => {
+      resolveEnded = resolve;
+    }),
+  };
+  // a child killed right after spawning still exits through 'close'
+  let spawned = false;
+  const endSession = (): void => {
+    if (deviceId && scrcpySessions.get(deviceId) === session) {
+      scrcpySessions.delete(deviceId);
+    }
+  };
+  // stopScrcpy may be called while any of the awaits below is pending
+  const assertNotStopped = (): void => {
+    if (session.state === "stopRequested") {
+      throw new Error(`Launch of scrcpy for ${deviceId} was cancelled`);
+    }
+  };
+  if (deviceId) {
+    scrcpySessions.set(deviceId, session);
+  }
+  try {
+    const adb = await resolveScrcpyAdb(options.adbPath);
+    const env = await scrcpyEnv(adb);
+    // not awaited, the warning is only a hint and must not delay the launch.
+    // Preferring the system adb was an explicit choice, nothing to warn about.
+    if (adb.path && adb.source !== "bundled" && !preferSystemAdb.value) {
+      checkAdbCompatibility(
+        (line) => callback(line, session.id),
+        adb.path
+      ).catch((error) => {
+        callback(`Failed to compare adb versions: ${error}\n`, session.id);
+      });
+    }
+    if (deviceId && !options.skipDeviceCheck) {
+      const devices = await listDevices();
+      if (!devices.some((device) => device.serial === deviceId)) {
+        throw new DeviceOfflineError(deviceId);
+      }
+    }
+    if (deviceId && options.waitForUnlock) {
+      const deadline = Date.now() + (options.unlockTimeoutMs ?? UNLOCK_TIMEOUT);
+      let waiting = false;
+      // an unrecognized keyguard state doesn't hold the launch
+      while (await isDeviceLocked(deviceId)) {
+        assertNotStopped();
+        if (Date.now() >= deadline) {
+          throw new Error(`${deviceId} was not unlocked in time`);
+        }
+        if (!waiting) {
+          waiting = true;
+          callback(`Waiting for ${deviceId} to be unlocked\n`, session.id);
+        }
+        await delay(UNLOCK_POLL_INTERVAL);
+      }
+    }
+    const launchArgs = [...args];
+    if (
+      !hasFlag(args, "--verbosity") &&
+      !args.some((arg) => arg.startsWith("-V"))
+    ) {
+      launchArgs.push(`--verbosity=${scrcpyVerbosity.value}`);
+    }
+    if (
+      deviceId &&
+      windowTitleTemplate.value.trim() &&
+      !hasFlag(args, "--window-title")
+    ) {
+      const title = await resolveWindowTitle(
+        windowTitleTemplate.value.trim(),
+        deviceId
+      );
+      launchArgs.push(`--window-title=${title}`);
+    }
+    assertNotStopped();
+    if (deviceId) {
+      scrcpyLogs.set(deviceId, []);
+    }
+    let transientError: string | undefined;
+    let wentOffline = false;
+    const onLine = (line: string): void => {
+      if (deviceId) {
+        appendScrcpyLog(deviceId, line);
+      }
+      if (!transientError && TRANSIENT_SCRCPY_ERROR.test(line)) {
+        transientError = line.trim();
+      }
+      wentOffline = wentOffline || isDeviceOfflineLine(line);
+      callback(line, session.id);
+      const hint = scrcpyHint(line);
+      if (hint) {
+        callback(`Hint: ${hint}\n`, session.id);
+      }
+    };
+    const command = new Command("scrcpy" + _binaryExtension, launchArgs, {
+      env,
+    });
+    command.on("error", onLine);
+    let spawnedAt = Date.now();
+    let lastStderr: string | undefined;
+    command.on('close', (data) => {
+      endSession();
+      resolveEnded();
+      if (deviceId) {
+        recordScrcpyExit(deviceId, {
+          code: data.code,
+          signal: data.signal,
+          exitedAt: Date.now(),
+          lastStderr,
+        });
+      }
+      const isEarlyFailure =
+        data.code !== 0 && Date.now() - spawnedAt < EARLY_EXIT_WINDOW;
+      onClose({
+        ...data,
+        sessionId: session.id,
+        retryReason: isEarlyFailure ? transientError : undefined,
+        offline:
+          wentOffline && deviceId
+            ? new DeviceOfflineError(deviceId)
+            : undefined,
+      });
+    });
+    command.stdout.on("data", onLine);
+    command.stderr.on("data", (line: string) => {
+      lastStderr = line.trim() || lastStderr;
+      onLine(line);
+    });
+
+    spawnedAt = Date.now();
+    const child = await command.spawn();
+    spawned = true;
+    if (session.state === "stopRequested") {
+      await child.kill();
+      assertNotStopped();
+    }
+    session.state = "running";
+    session.child = child;
+    session.launchArgs = launchArgs;
+    return child;
+  } catch (error) {
+    endSession();
+    if (!spawned) {
+      resolveEnded();
+    }
+    throw error;
+  }
+};
+
+export type ScrcpyOptions = {
+  maxSize?: number;
+  // e.g. "8M" or "800K"
+  bitRate?: string;
+  maxFps?: number;
+  // width:height:x:y
+  crop?: string;
+  videoCodec?: "h264" | "h265" | "av1";
+  // false adds --no-audio, scrcpy forwards audio by default
+  audio?: boolean;
+  // forward audio without mirroring the screen, scrcpy's --no-video
+  audioOnly?: boolean;
+  audioSource?: "output" | "mic";
+  audioCodec?: "opus" | "aac" | "flac" | "raw";
+  // initial window geometry, x/y may be negative on multi-monitor setups
+  windowX?: number;
+  windowY?: number;
+  windowWidth?: number;
+  windowHeight?: number;
+  alwaysOnTop?: boolean;
+  borderless?: boolean;
+  fullscreen?: boolean;
+  // index into availableMonitors() to open the window on, see
+  // startScrcpyTyped. Conflicts with windowX/windowY.
+  monitorIndex?: number;
+};
+const isPositiveInteger = (value: number): boolean => {
+  return Number.isInteger(value) && value > 0;
+};
+// throws on out of range values instead of letting scrcpy exit tersely
+export const buildScrcpyArgs = (options: ScrcpyOptions): string[] => {
+  const args: string[] = [];
+  if (options.maxSize !== undefined) {
+    if (!isPositiveInteger(options.maxSize)) {
+      throw new Error(`Invalid max size: ${options.maxSize}`);
+    }
+    args.push(`--max-size=${options.maxSize}`);
+  }
+  if (options.bitRate !== undefined) {
+    if (!/^\d+[KM]?$/i.test(options.bitRate)) {
+      throw new Error(`Invalid bit rate: ${options.bitRate}`);
+    }
+    args.push(`--video-bit-rate=${options.bitRate}`);
+  }
+  if (options.maxFps !== undefined) {
+    if (!isPositiveInteger(options.maxFps) || options.maxFps > 120) {
+      throw new Error(`Max FPS must be between 1 and 120: ${options.maxFps}`);
+    }
+    args.push(`--max-fps=${options.maxFps}`);
+  }
+  if (options.crop !== undefined) {
+    if (!/^\d+:\d+:\d+:\d+$/.test(options.crop)) {
+      throw new Error(`Crop must be width:height:x:y: ${options.crop}`);
+    }
+    args.push(`--crop=${options.crop}`);
+  }
+  if (options.videoCodec !== undefined) {
+    if (!["h264", "h265", "av1"].includes(options.videoCodec)) {
+      throw new Error(`Unsupported video codec: ${options.videoCodec}`);
+    }
+    args.push(`--video-codec=${options.videoCodec}`);
+  }
+  if (options.audioOnly) {
+    if (options.audio === false) {
+      throw new Error("Audio only mode requires audio to be enabled");
+    }
+    args.push("--no-video");
+  }
+  if (options.audio === false) {
+    args.push("--no-audio");
+  }
+  if (options.audioSource !== undefined) {
+    if (!["output", "mic"].includes(options.audioSource)) {
+      throw new Error(`Unsupported audio source: ${options.audioSource}`);
+    }
+    args.push(`--audio-source=${options.audioSource}`);
+  }
+  if (options.audioCodec !== undefined) {
+    if (!["opus", "aac", "flac", "raw"].includes(options.audioCodec)) {
+      throw new Error(`Unsupported audio codec: ${options.audioCodec}`);
+    }
+    args.push(`--audio-codec=${options.audioCodec}`);
+  }
+  const position: [string, number | undefined][] = [
+    ["--window-x", options.windowX],
+    ["--window-y", options.windowY],
+  ];
+  for (const [flag, value] of position) {
+    if (value === undefined) {
+      continue;
+    }
+    if (!Number.isInteger(value) || Math.abs(value) > 100000) {
+      throw new Error(`Invalid ${flag}: ${value}`);
+    }
+    args.push(`${flag}=${value}`);
+  }
+  const size: [string, number | undefined][] = [
+    ["--window-width", options.windowWidth],
+    ["--window-height", options.windowHeight],
+  ];
+  for (const [flag, value] of size) {
+    if (value === undefined) {
+      continue;
+    }
+    if (!isPositiveInteger(value) || value > 100000) {
+      throw new Error(`${flag} must be a positive integer: ${value}`);
+    }
+    args.push(`${flag}=${value}`);
+  }
+  if (options.alwaysOnTop) {
+    args.push("--always-on-top");
+  }
+  if (options.borderless) {
+    args.push("--window-borderless");
+  }
+  if (options.fullscreen) {
+    args.push("--fullscreen");
+  }
+  return args;
+};
+// scrcpy can't pick a monitor, so the window is opened at the top-left of
+// the chosen one and --fullscreen then fills the monitor the window is on.
+// Window managers that ignore position hints, such as most Wayland
+// compositors, still place it wherever they like.
+const placeOnMonitor = async (
+  options: ScrcpyOptions
+): Promise<ScrcpyOptions> => {
+  const { monitorIndex } = options;
+  if (monitorIndex === undefined) {
+    return options;
+  }
+  if (options.windowX !== undefined || options.windowY !== undefined) {
+    throw new Error("monitorIndex can't be combined with windowX/windowY");
+  }
+  const monitors = await availableMonitors();
+  const monitor = monitors[monitorIndex];
+  if (!Number.isInteger(monitorIndex) || !monitor) {
+    throw new Error(
+      `Invalid monitor ${monitorIndex}, ${monitors.length} monitors are available`
+    );
+  }
+  // SDL works in points on macOS and in pixels elsewhere
+  const { x, y } =
+    (await type()) === "Darwin"
+      ? monitor.position.toLogical(monitor.scaleFactor)
+      : monitor.position;
+  return { ...options, windowX: Math.round(x), windowY: Math.round(y) };
+};
+// `extraArgs` are passed through verbatim after the typed options
+export const startScrcpyTyped = async (
+  deviceId: string,
+  options: ScrcpyOptions,
+  extraArgs: string[],
+  callback: (line: string, sessionId: number) => void,
+  onClose: (data: ScrcpyExit) => void,
+  startOptions: StartScrcpyOptions = {}
+): Promise<Child> => {
+  const args = ["-s", deviceId]
+    .concat(buildScrcpyArgs(await placeOnMonitor(options)))
+    .concat(extraArgs);
+  return startScrcpy(args, callback, onClose, startOptions);
+};
+
+// "tcp:8080" or "localabstract:name"
+const isValidSocketSpec = (spec: string): boolean => {
+  const [, port] = spec.match(/^tcp:(\d+)$/) || [];
+  if (port) {
+    return Number(port) > 0 && Number(port) <= 65535;
+  }
+  return /^localabstract:[\w.-]+$/.test(spec);
+};
+const assertSocketSpecs = (...specs: string[]): void => {
+  const invalid = specs.find((spec) => !isValidSocketSpec(spec));
+  if (invalid !== undefined) {
+    throw new Error(
+      `Invalid socket spec "${invalid}", expected tcp:<port> or localabstract:<name>`
+    );
+  }
+};
+// host `local` -> device `remote`
+export const adbForward = async (
+  deviceId: string,
+  local: string,
+  remote: string
+): Promise<void> => {
+  assertSocketSpecs(local, remote);
+  await executeAdbChecked(["-s", deviceId, "forward", local, remote]);
+};
+// device `remote` -> host `local`
+export const adbReverse = async (
+  deviceId: string,
+  remote: string,
+  local: string
+): Promise<void> => {
+  assertSocketSpecs(remote, local);
+  await executeAdbChecked(["-s", deviceId, "reverse", remote, local]);
+};
+export type PortForward = { local: string; remote: string };
+export const listForwards = async (
+  deviceId: string
+): Promise<PortForward[]> => {
+  const { stdout } = await executeAdbChecked(["forward", "--list"]);
+  return stdout
+    .split("\n")
+    .map((line) => line.trim().split(/\s+/))
+    .filter(([serial, , remote]) => serial === deviceId && remote)
+    .map(([, local, remote]) => ({ local, remote }));
+};
+
+const CLIPBOARD_UNAVAILABLE =
+  /^(Unknown command|No shell command implementation|Can't find service)/im;
+const runClipboardCommand = async (
+  deviceId: string,
+  command: string[]
+): Promise<string> => {
+  const { stdout, stderr, exitCode } = await runAdbShell(deviceId, [
+    "cmd",
+    "clipboard",
+    ...command,
+  ]);
+  if (exitCode !== 0 || CLIPBOARD_UNAVAILABLE.test(stderr || stdout)) {
+    const reason = (stderr || stdout).trim();
+    throw new Error(
+      `The clipboard service is not available over adb on this device: ${reason}`
+    );
+  }
+  return stdout;
+};
+// sets the device clipboard without a running mirror
+export const setDeviceClipboard = async (
+  deviceId: string,
+  text: string
+): Promise<void> => {
+  await runClipboardCommand(deviceId, [
+    "set-primary-clip",
+    quoteDeviceShellArg(text),
+  ]);
+};
+export const getDeviceClipboard = async (deviceId: string): Promise<string> => {
+  // prints "null" when the clipboard is empty
+  const output = (await runClipboardCommand(deviceId, ["get-primary-clip"]))
+    .replace(/\r?\n$/, "");
+  return output === "null" ? "" : output;
+};
+
+const executeScrcpy = async (
+  args: string[],
+  env?: Record<string, string>
+): Promise<ChildProcess> => {
+  return new Command("scrcpy" + _binaryExtension, args, {
+    env: env || (await toolEnv()),
+  }).execute();
+};
+// "2.3.1" out of "scrcpy 2.3.1 <https://github.com/Genymobile/scrcpy>"
+export const getScrcpyVersion = async (): Promise<string> => {
+  const { stdout } = await executeScrcpy(["--version"]);
+  const [, version] = stdout.match(/^scrcpy v?(\S+)/m) || [];
+  if (!version) {
+    throw new Error(`Unexpected scrcpy --version output: ${stdout.trim()}`);
+  }
+  return version;
+};
+
+export type ScrcpyHelp = {
+  version: string;
+  text: string;
+  // long options documented by this version, e.g. "--max-fps"
+  flags: string[];
+};
+let scrcpyHelpCache: ScrcpyHelp | undefined;
+// --help only runs again when the installed scrcpy version changes
+export const getScrcpyHelp = async (): Promise<ScrcpyHelp> => {
+  const version = await getScrcpyVersion();
+  if (scrcpyHelpCache?.version === version) {
+    return scrcpyHelpCache;
+  }
+  const { stdout } = await executeScrcpy(["--help"]);
+  const flags = [...stdout.matchAll(/^\s*(?:-\w, )?(--[a-z0-9][\w-]*)/gm)]
+    .map(([, flag]) => flag)
+    .filter((flag, index, all) => all.indexOf(flag) === index);
+  scrcpyHelpCache = { version, text: stdout, flags };
+  return scrcpyHelpCache;
+};
+
+// "KEYCODE_HOME", "HOME" or a numeric code such as "3"
+const isValidKeycode = (keycode: string): boolean => {
+  if (/^\d+$/.test(keycode)) {
+    return Number(keycode) < 1000;
+  }
+  return /^(KEYCODE_)?[A-Z0-9_]+$/.test(keycode);
+};
+export const sendKeyevent = async (
+  deviceId: string,
+  keycode: string
+): Promise<void> => {
+  if (!isValidKeycode(keycode)) {
+    throw new Error(`Invalid keycode: ${keycode}`);
+  }
+  const name =
+    /^\d+$/.test(keycode) || keycode.startsWith("KEYCODE_")
+      ? keycode
+      : `KEYCODE_${keycode}`;
+  await executeAdbChecked([
+    "-s",
+    deviceId,
+    "shell",
+    "input",
+    "keyevent",
+    name,
+  ]);
+};
+// `input text` reads "%s" as a space and splits its arguments on spaces
+export const sendText = async (
+  deviceId: string,
+  text: string
+): Promise<void> => {
+  if (!text) {
+    return;
+  }
+  await executeAdbChecked([
+    "-s",
+    deviceId,
+    "shell",
+    "input",
+    "text",
+    quoteDeviceShellArg(text.replace(/ /g, "%s")),
+  ]);
+};
+
+// raw `wm size` / `wm density` readouts, e.g. "Physical size: 1080x2400"
+// followed by "Override size: 720x1600" while an override is active
+export type DisplayMetrics = { size: string; density: string };
+const readDisplayMetrics = async (
+  deviceId: string
+): Promise<DisplayMetrics> => {
+  const [size, density] = await Promise.all([
+    runAdbShell(deviceId, ["wm", "size"]),
+    runAdbShell(deviceId, ["wm", "density"]),
+  ]);
+  return { size: size.stdout.trim(), density: density.stdout.trim() };
+};
+// temporary until resetDisplayMetrics or a reboot of the device
+export const setDisplayMetrics = async (
+  deviceId: string,
+  size?: string,
+  density?: number
+): Promise<DisplayMetrics> => {
+  if (size !== undefined && !/^[1-9]\d*x[1-9]\d*$/.test(size)) {
+    throw new Error(`Display size must be WIDTHxHEIGHT: ${size}`);
+  }
+  if (density !== undefined && !isPositiveInteger(density)) {
+    throw new Error(`Invalid display density: ${density}`);
+  }
+  if (size !== undefined) {
+    await executeAdbChecked(["-s", deviceId, "shell", "wm", "size", size]);
+  }
+  if (density !== undefined) {
+    await executeAdbChecked([
+      "-s",
+      deviceId,
+      "shell",
+      "wm",
+      "density",
+      density.toString(),
+    ]);
+  }
+  return readDisplayMetrics(deviceId);
+};
+export const resetDisplayMetrics = async (
+  deviceId: string
+): Promise<DisplayMetrics> => {
+  await executeAdbChecked(["-s", deviceId, "shell", "wm", "size", "reset"]);
+  await executeAdbChecked(["-s", deviceId, "shell", "wm", "density", "reset"]);
+  return readDisplayMetrics(deviceId);
+};
+
+export type ApkInstallResult = { success: boolean; output: string };
+// Installs the split APKs of one app in a single session, e.g. the output
+// of bundletool. adb progress lines are forwarded to `onProgress`.
+export const installApks = async (
+  deviceId: string,
+  apks: string[],
+  onProgress: (line: string) => void
+): Promise<ApkInstallResult> => {
+  if (!apks.length) {
+    throw new Error("No APK files given");
+  }
+  for (const apk of apks) {
+    if (!apk.toLowerCase().endsWith(".apk") || !(await isFile(apk))) {
+      throw new Error(`Not an APK file: ${apk}`);
+    }
+  }
+  const command = await adbCommand([
+    "-s",
+    deviceId,
+    "install-multiple",
+    ...apks,
+  ]);
+  const lines: string[] = [];
+  const onLine = (line: string): void => {
+    lines.push(line);
+    onProgress(line);
+  };
+  command.stdout.on("data", onLine);
+  command.stderr.on("data", onLine);
+  return new Promise((resolve, reject) => {
+    command.on("error", reject);
+    command.on("close", ({ code }) => {
+      const output = lines.join("");
+      resolve({ success: code === 0 && /\bSuccess\b/.test(output), output });
+    });
+    command.spawn().catch(reject);
+  });
+};
+
+// "device", "offline", "unauthorized", "authorizing"... or "unknown" when adb
+// can't tell, e.g. for a serial it doesn't know
+export const getDeviceState = async (serial: string): Promise<string> => {
+  const { stdout, stderr, code } = await executeAdb([
+    "-s",
+    serial,
+    "get-state",
+  ]);
+  if (code === 0) {
+    return stdout.trim();
+  }
+  // "error: device unauthorized." or "error: device still authorizing"
+  const [, state] =
+    stderr.match(
+      /error: device (?:still )?(unauthorized|authorizing|offline)/
+    ) || [];
+  return state || "unknown";
+};
+// `adb wait-for-device` blocks forever for a device that never comes back,
+// it is killed after timeoutMs. Resolves with whether the device is ready,
+// e.g. after a reboot before launching a mirror.
+export const waitForDevice = async (
+  deviceId: string,
+  timeoutMs: number
+): Promise<boolean> => {
+  const command = await adbCommand([
+    "-s",
+    deviceId,
+    "wait-for-device",
+  ]);
+  const exited = new Promise<boolean>((resolve) => {
+    command.on("close", ({ code }) => resolve(code === 0));
+    command.on("error", () => resolve(false));
+  });
+  const child = await command.spawn();
+  let timer: ReturnType<typeof setTimeout> | undefined;
+  const timedOut = new Promise<boolean>((resolve) => {
+    timer = setTimeout(() => resolve(false), timeoutMs);
+  });
+  const ready = await Promise.race([exited, timedOut]);
+  clearTimeout(timer);
+  if (!ready) {
+    await child.kill().catch(() => undefined);
+  }
+  return ready;
+};
+
+const AUTHORIZATION_POLL_INTERVAL = 1000;
+// Polls the device until the user accepted the RSA prompt on it or timeoutMs
+// elapsed, calling onStatus whenever the state changes. Resolves with the
+// last state, "device" once authorized.
+export const waitForAuthorization = async (
+  deviceId: string,
+  timeoutMs: number,
+  onStatus?: (state: string) => void
+): Promise<string> => {
+  const deadline = Date.now() + timeoutMs;
+  let previous: string | undefined;
+  for (;;) {
+    const state = await getDeviceState(deviceId);
+    if (state !== previous) {
+      previous = state;
+      onStatus?.(state);
+    }
+    if (state === "device" || Date.now() >= deadline) {
+      return state;
+    }
+    await delay(
+      Math.min(AUTHORIZATION_POLL_INTERVAL, Math.max(0, deadline - Date.now()))
+    );
+  }
+};
+
+const RECONNECT_ATTEMPTS = 3;
+// The disconnect/connect dance for a wireless device that went offline after
+// sleeping or roaming. Resolves with "device", rejects when it stays offline.
+export const reconnectWirelessDevice = async (
+  serial: string
+): Promise<string> => {
+  if (!isWirelessSerial(serial) || serial.includes("._adb-tls-connect.")) {
+    throw new Error(`${serial} is not a host:port wireless device`);
+  }
+  let state = "unknown";
+  for (let attempt = 1; attempt <= RECONNECT_ATTEMPTS; attempt++) {
+    await executeAdb(["disconnect", serial]);
+    await executeAdb(["connect", serial]);
+    // a relaunch right after must not reuse a listing from before
+    invalidateDeviceList();
+    state = await getDeviceState(serial);
+    if (state === "device") {
+      return state;
+    }
+    if (attempt < RECONNECT_ATTEMPTS) {
+      await delay(attempt * 1000);
+    }
+  }
+  throw new Error(
+    `${serial} is still ${state} after ${RECONNECT_ATTEMPTS} reconnect attempts, check that the device is on the same network and wireless debugging is enabled`
+  );
+};
+
+const SCRCPY_LATEST_RELEASE_URL =
+  "https://api.github.com/repos/Genymobile/scrcpy/releases/latest";
+// numeric comparison of dotted versions, "2.10" is newer than "2.9"
+export const compareVersions = (left: string, right: string): number => {
+  const leftParts = left.split(".").map((part) => parseInt(part, 10) || 0);
+  const rightParts = right.split(".").map((part) => parseInt(part, 10) || 0);
+  for (let i = 0; i < Math.max(leftParts.length, rightParts.length); i++) {
+    const difference = (leftParts[i] || 0) - (rightParts[i] || 0);
+    if (difference) {
+      return difference;
+    }
+  }
+  return 0;
+};
+export type ScrcpyUpdate = {
+  current: string;
+  latest: string;
+  updateAvailable: boolean;
+};
+// compares the installed scrcpy with the latest GitHub release, nothing is
+// downloaded
+export const checkScrcpyUpdate = async (): Promise<ScrcpyUpdate> => {
+  const [current, response] = await Promise.all([
+    getScrcpyVersion(),
+    fetch<{ tag_name?: string }>(SCRCPY_LATEST_RELEASE_URL, {
+      method: "GET",
+      headers: {
+        Accept: "application/vnd.github+json",
+        "User-Agent": "scrcpy-gui",
+      },
+    }),
+  ]);
+  if (
+    (response.status === 403 || response.status === 429) &&
+    response.headers["x-ratelimit-remaining"] === "0"
+  ) {
+    const reset = Number(response.headers["x-ratelimit-reset"]) * 1000;
+    throw new Error(
+      `GitHub API rate limit reached, try again after ${new Date(
+        reset
+      ).toLocaleTimeString()}`
+    );
+  }
+  if (!response.ok || !response.data.tag_name) {
+    throw new Error(
+      `Failed to fetch the latest scrcpy release: HTTP ${response.status}`
+    );
+  }
+  const latest = response.data.tag_name.replace(/^v/, "");
+  return {
+    current,
+    latest,
+    updateAvailable: compareVersions(latest, current) > 0,
+  };
+};
+
+const DIAGNOSTICS_APP_LOG_LINES = 1000;
+// settles to the error message so one failing probe doesn't void the bundle
+const describe = async <T>(probe: () => Promise<T>): Promise<T | string> => {
+  try {
+    return await probe();
+  } catch (error) {
+    return `error: ${error}`;
+  }
+};
+// Writes everything a bug report needs into one JSON file. `dest` must be
+// in the fs scope, e.g. a path picked with the save dialog.
+export const exportDiagnosticsBundle = async (
+  dest: string,
+  appLog: string
+): Promise<void> => {
+  const bundle = {
+    createdAt: new Date().toISOString(),
+    os: {
+      type: await describe(type),
+      version: await describe(version),
+      arch: await describe(arch),
+    },
+    toolPaths: await describe(getToolPaths),
+    versions: {
+      adb: await describe(() => getAdbVersion()),
+      scrcpy: await describe(getScrcpyVersion),
+    },
+    devices: await describe(() => listDevices()),
+    scrcpyLogs: Object.fromEntries(scrcpyLogs),
+    appLog: appLog.split("\n").slice(-DIAGNOSTICS_APP_LOG_LINES),
+  };
+  await writeTextFile(dest, JSON.stringify(bundle, null, 2));
+};
+
+export type DeveloperOverlay = { showTaps: boolean; pointerLocation: boolean };
+const DEVELOPER_OVERLAY_SETTINGS = {
+  showTaps: "show_touches",
+  pointerLocation: "pointer_location",
+} as const;
+const readSetting = async (
+  deviceId: string,
+  namespace: "system" | "global" | "secure",
+  name: string
+): Promise<string> => {
+  const { stdout } = await executeAdbChecked([
+    "-s",
+    deviceId,
+    "shell",
+    "settings",
+    "get",
+    namespace,
+    name,
+  ]);
+  return stdout.trim();
+};
+export const getDeveloperOverlay = async (
+  deviceId: string
+): Promise<DeveloperOverlay> => {
+  const [showTaps, pointerLocation] = await Promise.all([
+    readSetting(deviceId, "system", DEVELOPER_OVERLAY_SETTINGS.showTaps),
+    readSetting(deviceId, "system", DEVELOPER_OVERLAY_SETTINGS.pointerLocation),
+  ]);
+  return {
+    showTaps: showTaps === "1",
+    pointerLocation: pointerLocation === "1",
+  };
+};
+// options left undefined are not touched, resolves with the values read back
+export const setDeveloperOverlay = async (
+  deviceId: string,
+  overlay: Partial<DeveloperOverlay>
+): Promise<DeveloperOverlay> => {
+  for (const key of Object.keys(DEVELOPER_OVERLAY_SETTINGS) as Array<
+    keyof DeveloperOverlay
+  >) {
+    const enabled = overlay[key];
+    if (enabled === undefined) {
+      continue;
+    }
+    const { stdout, stderr } = await runAdbShell(deviceId, [
+      "settings",
+      "put",
+      "system",
+      DEVELOPER_OVERLAY_SETTINGS[key],
+      enabled ? "1" : "0",
+    ]);
+    // some vendor builds reject writes from the shell user
+    if (/SecurityException|Permission denial/i.test(stdout + stderr)) {
+      throw new Error(
+        `The device refused to change ${DEVELOPER_OVERLAY_SETTINGS[key]}, it requires a permission adb shell doesn't have on this device`
+      );
+    }
+  }
+  return getDeveloperOverlay(deviceId);
+};
+
+export type PingResult = {
+  status: "reachable" | "refused" | "timeout" | "unresolved" | "unreachable";
+  latencyMs: number | null;
+  message: string | null;
+};
+// checks that host:port accepts TCP connections before trying adb connect
+export const pingWirelessTarget = (
+  host: string,
+  port: number
+): Promise<PingResult> => {
+  return invoke<PingResult>("ping_wireless_target", { host, port });
+};
+
+export type DeviceAddress = { interface: string; address: string };
+// "    inet 192.168.1.23/24 brd 192.168.1.255 scope global wlan0"
+const parseIpAddr = (output: string): DeviceAddress[] => {
+  return [
+    ...output.matchAll(/inet (\d+\.\d+\.\d+\.\d+)\/\d+.*?(\S+)\s*$/gm),
+  ].map(([, address, name]) => ({ interface: name, address }));
+};
+// "192.168.1.0/24 dev wlan0 proto kernel scope link src 192.168.1.23"
+const parseIpRoute = (output: string): DeviceAddress[] => {
+  return [...output.matchAll(/dev (\S+).*\bsrc (\d+\.\d+\.\d+\.\d+)/g)].map(
+    ([, name, address]) => ({ interface: name, address })
+  );
+};
+// "wlan0     Link encap:UNSPEC" followed by "  inet addr:192.168.1.23 ..."
+const parseIfconfig = (output: string): DeviceAddress[] => {
+  const addresses: DeviceAddress[] = [];
+  let name = "";
+  for (const line of output.split("\n")) {
+    if (/^\S/.test(line)) {
+      name = line.split(/\s+/)[0].replace(/:$/, "");
+    }
+    const [, address] = line.match(/inet (?:addr:)?(\d+\.\d+\.\d+\.\d+)/) || [];
+    if (name && address) {
+      addresses.push({ interface: name, address });
+    }
+  }
+  return addresses;
+};
+// IPv4 addresses of the device except loopback, wlan0 first. Tries
+// `ip addr`, `ip route` and `ifconfig`, then the DHCP lease property of
+// older releases.
+const readDeviceAddresses = async (
+  deviceId: string
+): Promise<DeviceAddress[]> => {
+  const sources: [string[], (output: string) => DeviceAddress[]][] = [
+    [["ip", "-f", "inet", "addr", "show"], parseIpAddr],
+    [["ip", "route"], parseIpRoute],
+    [["ifconfig"], parseIfconfig],
+  ];
+  let addresses: DeviceAddress[] = [];
+  for (const [command, parse] of sources) {
+    const { stdout } = await runAdbShell(deviceId, command);
+    addresses = parse(stdout).filter(
+      ({ address }, index, all) =>
+        !address.startsWith("127.") &&
+        all.findIndex((other) => other.address === address) === index
+    );
+    if (addresses.length) {
+      break;
+    }
+  }
+  if (!addresses.length) {
+    const lease = await readProp(deviceId, "dhcp.wlan0.ipaddress");
+    if (/^\d+\.\d+\.\d+\.\d+$/.test(lease)) {
+      addresses.push({ interface: "wlan0", address: lease });
+    }
+  }
+  return addresses.sort((left, right) =>
+    left.interface === "wlan0" ? -1 : right.interface === "wlan0" ? 1 : 0
+  );
+};
+export type DeviceAddressList = {
+  addresses: DeviceAddress[];
+  // why the list is empty
+  message?: string;
+};
+// e.g. to prefill the address of a wireless connect after enableWireless
+export const getDeviceIp = async (
+  deviceId: string
+): Promise<DeviceAddressList> => {
+  const addresses = await readDeviceAddresses(deviceId);
+  return addresses.length
+    ? { addresses }
+    : {
+        addresses,
+        message: `${deviceId} has no network address, is it connected to Wi-Fi?`,
+      };
+};
+
+const isConnectedOutput = (output: string): boolean => {
+  return /\bconnected to\b/.test(output) && !/failed|cannot/i.test(output);
+};
+
+const WIFI_INTERFACE = /^wlan\d|^swlan/;
+
+// The plug-in-then-go-wireless flow: read the device IP over USB, switch adbd
+// to TCP mode and connect. Resolves with the new "ip:port" serial.
+export const enableWireless = async (
+  deviceId: string,
+  port = 5555
+): Promise<string> => {
+  if (!Number.isInteger(port) || port <= 0 || port > 65535) {
+    throw new Error(`Invalid port: ${port}`);
+  }
+  // a mobile data address such as rmnet_data0 is no use for adb over Wi-Fi
+  const wlan = (await readDeviceAddresses(deviceId)).find((address) =>
+    WIFI_INTERFACE.test(address.interface)
+  );
+  if (!wlan) {
+    throw new Error(`${deviceId} has no Wi-Fi address, connect it to Wi-Fi`);
+  }
+  await executeAdbChecked(["-s", deviceId, "tcpip", port.toString()]);
+  const serial = `${wlan.address}:${port}`;
+  // adbd restarts in TCP mode, give it a moment before each attempt
+  for (let attempt = 1; attempt <= RECONNECT_ATTEMPTS; attempt++) {
+    await delay(attempt * 1000);
+    const { stdout, stderr } = await executeAdb(["connect", serial]);
+    if (isConnectedOutput(stdout + stderr)) {
+      invalidateDeviceList();
+      return serial;
+    }
+  }
+  throw new Error(
+    `Device switched to tcpip but connecting to ${serial} failed`
+  );
+};
+
+// e.g. the file a `--record` launch wrote once its mirror closed
+export const revealFile = (path: string): Promise<void> => {
+  return invoke<void>("reveal_file", { path });
+};
+
+const isPermissionDenied = (output: string): boolean => {
+  return /SecurityException|Permission denial/i.test(output);
+};
+const isUnknownShellCommand = (result: AdbShellResult): boolean => {
+  return (
+    result.exitCode !== 0 ||
+    /Unknown command|No shell command implementation|not found/i.test(
+      result.stdout + result.stderr
+    )
+  );
+};
+// Connectivity changes settle asynchronously, poll the setting for a moment
+// and resolve with its last value.
+const waitForSetting = async (
+  deviceId: string,
+  name: string,
+  matches: (value: string) => boolean
+): Promise<string> => {
+  let value = await readSetting(deviceId, "global", name);
+  for (let attempt = 0; attempt < 10 && !matches(value); attempt++) {
+    await delay(200);
+    value = await readSetting(deviceId, "global", name);
+  }
+  return value;
+};
+
+export const getAirplaneMode = async (deviceId: string): Promise<boolean> => {
+  return (await readSetting(deviceId, "global", "airplane_mode_on")) === "1";
+};
+// `cmd connectivity airplane-mode` exists since Android 11. Older releases
+// only take the setting plus its broadcast, which shell may not send since
+// Android 7 and then needs root. Resolves with the mode read back.
+export const setAirplaneMode = async (
+  deviceId: string,
+  enabled: boolean
+): Promise<boolean> => {
+  const result = await runAdbShell(deviceId, [
+    "cmd",
+    "connectivity",
+    "airplane-mode",
+    enabled ? "enable" : "disable",
+  ]);
+  if (isPermissionDenied(result.stdout + result.stderr)) {
+    throw new Error("Changing airplane mode requires root on this device");
+  }
+  if (isUnknownShellCommand(result)) {
+    await executeAdbChecked([
+      "-s",
+      deviceId,
+      "shell",
+      "settings",
+      "put",
+      "global",
+      "airplane_mode_on",
+      enabled ? "1" : "0",
+    ]);
+    const broadcast = await runAdbShell(deviceId, [
+      "am",
+      "broadcast",
+      "-a",
+      "android.intent.action.AIRPLANE_MODE",
+      "--ez",
+      "state",
+      enabled.toString(),
+    ]);
+    if (isPermissionDenied(broadcast.stdout + broadcast.stderr)) {
+      throw new Error(
+        "The airplane mode setting changed but the device refused the broadcast that applies it, this requires root on this device"
+      );
+    }
+  }
+  const value = await waitForSetting(
+    deviceId,
+    "airplane_mode_on",
+    (current) => (current === "1") === enabled
+  );
+  return value === "1";
+};
+
+// wifi_on is 1 or 2 (kept on in airplane mode) while Wi-Fi is enabled
+const isWifiOnValue = (value: string): boolean => {
+  return value === "1" || value === "2";
+};
+export const getWifiEnabled = async (deviceId: string): Promise<boolean> => {
+  return isWifiOnValue(await readSetting(deviceId, "global", "wifi_on"));
+};
+// Prefers `cmd wifi` (Android 11+) over `svc wifi`, resolves with the state
+// read back. Turning Wi-Fi off drops wireless adb connections to the device.
+export const setWifiEnabled = async (
+  deviceId: string,
+  enabled: boolean
+): Promise<boolean> => {
+  let result = await runAdbShell(deviceId, [
+    "cmd",
+    "wifi",
+    "set-wifi-enabled",
+    enabled ? "enabled" : "disabled",
+  ]);
+  if (isUnknownShellCommand(result)) {
+    result = await runAdbShell(deviceId, [
+      "svc",
+      "wifi",
+      enabled ? "enable" : "disable",
+    ]);
+  }
+  if (isPermissionDenied(result.stdout + result.stderr)) {
+    throw new Error("Changing Wi-Fi requires root on this device");
+  }
+  const value = await waitForSetting(
+    deviceId,
+    "wifi_on",
+    (current) => isWifiOnValue(current) === enabled
+  );
+  return isWifiOnValue(value);
+};
+
+// "topResumedActivity=ActivityRecord{9c3f1a2 u0 com.example/.MainActivity t12}"
+// on Android 10+, "mResumedActivity: ActivityRecord{...}" before that
+const RESUMED_ACTIVITY_PATTERNS = [
+  /topResumedActivity=ActivityRecord\{\S+ u\d+ ([\w.]+)\//,
+  /mResumedActivity: ActivityRecord\{\S+ u\d+ ([\w.]+)\//,
+];
+// "mCurrentFocus=Window{4a1b2c3 u0 com.example/com.example.MainActivity}"
+const FOCUSED_WINDOW_PATTERNS = [
+  /mCurrentFocus=Window\{\S+ u\d+ ([\w.]+)\//,
+  /mFocusedApp=.*? u\d+ ([\w.]+)\//,
+];
+const matchPackage = (
+  output: string,
+  patterns: RegExp[]
+): string | undefined => {
+  for (const pattern of patterns) {
+    const [, packageName] = output.match(pattern) || [];
+    if (packageName) {
+      return packageName;
+    }
+  }
+  return undefined;
+};
+// package of the app on screen, undefined when nothing is resumed such as on
+// the lock screen. dumpsys is filtered on the device, its full output easily
+// exceeds what runAdbShell keeps.
+export const getForegroundApp = async (
+  deviceId: string
+): Promise<string | undefined> => {
+  const { stdout: activities } = await runAdbShell(deviceId, [
+    "dumpsys",
+    "activity",
+    "activities",
+    "|",
+    "grep",
+    "ResumedActivity",
+  ]);
+  const resumed = matchPackage(activities, RESUMED_ACTIVITY_PATTERNS);
+  if (resumed) {
+    return resumed;
+  }
+  const { stdout: windows } = await runAdbShell(deviceId, [
+    "dumpsys",
+    "window",
+    "windows",
+    "|",
+    "grep",
+    "-E",
+    quoteDeviceShellArg("mCurrentFocus|mFocusedApp"),
+  ]);
+  return matchPackage(windows, FOCUSED_WINDOW_PATTERNS);
+};
+
+export type DeviceCamera = {
+  id: string;
+  // "back", "front" or "external"
+  facing: string;
+  // active array size, e.g. "4000x3000"
+  size: string;
+  fps: number[];
+  // output sizes usable with --camera-size, largest first
+  sizes: string[];
+};
+export type DeviceCameraList = {
+  cameras: DeviceCamera[];
+  // why the list is empty, e.g. an Android or scrcpy version without cameras
+  message?: string;
+};
+// "    --camera-id=0    (back, 4000x3000, fps=[15, 24, 30])"
+const CAMERA_LINE = /--camera-id=(\S+)\s+\((\w+), (\d+x\d+)(?:, fps=\[([\d, ]*)\])?/;
+// "        - 1920x1080"
+const CAMERA_SIZE_LINE = /^\s+- (\d+x\d+)\s*$/;
+export const listDeviceCameras = async (
+  deviceId: string
+): Promise<DeviceCameraList> => {
+  // pushes the server like a launch does
+  const { stdout, stderr } = await executeScrcpy(
+    ["-s", deviceId, "--list-camera-sizes"],
+    await scrcpyEnv()
+  );
+  const output = stdout + stderr;
+  const cameras: DeviceCamera[] = [];
+  for (const line of output.split("\n")) {
+    const [, id, facing, size, fps] = line.match(CAMERA_LINE) || [];
+    if (id) {
+      cameras.push({
+        id,
+        facing,
+        size,
+        fps: (fps || "").split(",").filter(Boolean).map(Number),
+        sizes: [],
+      });
+      continue;
+    }
+    const [, outputSize] = line.match(CAMERA_SIZE_LINE) || [];
+    if (outputSize && cameras.length) {
+      cameras[cameras.length - 1].sizes.push(outputSize);
+    }
+  }
+  if (cameras.length) {
+    return { cameras };
+  }
+  if (/unrecognized option|unknown option/i.test(output)) {
+    return {
+      cameras,
+      message: "This scrcpy version can't mirror cameras, it requires 2.1+",
+    };
+  }
+  const [, error] = output.match(/ERROR: (.+)/) || [];
+  return {
+    cameras,
+    message: error?.trim() || "The device reported no cameras",
+  };
+};
+
+export const formatBytes = (bytes: number): string => {
+  const units = ["B", "KB", "MB", "GB", "TB"];
+  let value = bytes;
+  let unit = 0;
+  while (value >= 1024 && unit < units.length - 1) {
+    value /= 1024;
+    unit++;
+  }
+  return `${unit ? value.toFixed(1) : value} ${units[unit]}`;
+};
+export type StorageUsage = {
+  // mount point the path lives on, e.g. "/data" or "/storage/emulated"
+  mountedOn: string;
+  totalBytes: number;
+  usedBytes: number;
+  availableBytes: number;
+  // formatBytes of the values above, e.g. "42.3 GB"
+  total: string;
+  used: string;
+  available: string;
+};
+export type DeviceStorage = { data?: StorageUsage; sdcard?: StorageUsage };
+// "/dev/block/dm-5  115164996 42022540  73011384  37% /data", -k keeps the
+// units the same whether the build's df defaults to -h or not
+const parseDfLine = (line: string): StorageUsage | undefined => {
+  const tokens = line.trim().split(/\s+/);
+  if (tokens.length < 5) {
+    return undefined;
+  }
+  const [total, used, available, percent, mountedOn] = tokens.slice(-5);
+  const blocks = [total, used, available].map(Number);
+  if (
+    !/^\d+%$/.test(percent) ||
+    blocks.some((value) => !Number.isInteger(value))
+  ) {
+    return undefined;
+  }
+  const [totalBytes, usedBytes, availableBytes] = blocks.map(
+    (value) => value * 1024
+  );
+  return {
+    mountedOn,
+    totalBytes,
+    usedBytes,
+    availableBytes,
+    total: formatBytes(totalBytes),
+    used: formatBytes(usedBytes),
+    available: formatBytes(availableBytes),
+  };
+};
+// A path missing on the device, or an old toolbox df without -k, leaves its
+// field undefined rather than failing the whole call.
+export const getDeviceStorage = async (
+  deviceId: string
+): Promise<DeviceStorage> => {
+  const read = async (path: string): Promise<StorageUsage | undefined> => {
+    const { stdout } = await runAdbShell(deviceId, ["df", "-k", path]);
+    const [, ...rows] = stdout.trim().split("\n");
+    return rows.map(parseDfLine).find((usage) => !!usage);
+  };
+  const [data, sdcard] = await Promise.all([read("/data"), read("/sdcard/")]);
+  return { data, sdcard };
+};
+
+// a backslash in front of a quote or another backslash
+const isEscape = (line: string, position: number): boolean => {
+  return line[position] === "\\" && /["'\\]/.test(line[position + 1] || "");
+};
+// Splits an args file into arguments: whitespace and newlines separate them,
+// single or double quotes keep spaces and "#" starts a comment at the start
+// of an argument. Outside single quotes a backslash only escapes a quote or
+// another backslash, so Windows paths like C:\videos\a.mp4 stay as written.
+// Every line has to start with a flag, values may follow it on that line.
+export const parseArgsFile = (content: string): string[] => {
+  const args: string[] = [];
+  content.split(/\r?\n/).forEach((line, index) => {
+    const lineArgs: string[] = [];
+    let current: string | undefined;
+    let quote: string | undefined;
+    for (let position = 0; position < line.length; position++) {
+      const char = line[position];
+      if (quote) {
+        if (char === quote) {
+          quote = undefined;
+        } else if (quote === '"' && isEscape(line, position)) {
+          current += line[++position];
+        } else {
+          current += char;
+        }
+      } else if (/\s/.test(char)) {
+        if (current !== undefined) {
+          lineArgs.push(current);
+          current = undefined;
+        }
+      } else if (char === "#" && current === undefined) {
+        break;
+      } else if (char === "'" || char === '"') {
+        quote = char;
+        current = current || "";
+      } else if (isEscape(line, position)) {
+        current = (current || "") + line[++position];
+      } else {
+        current = (current || "") + char;
+      }
+    }
+    if (quote) {
+      throw new Error(`Line ${index + 1}: unterminated ${quote} quote`);
+    }
+    if (current !== undefined) {
+      lineArgs.push(current);
+    }
+    if (lineArgs.length && !lineArgs[0].startsWith("-")) {
+      throw new Error(
+        `Line ${index + 1}: expected a flag such as --max-size, got ${lineArgs[0]}`
+      );
+    }
+    args.push(...lineArgs);
+  });
+  return args;
+};
+// Launches `deviceId` with the arguments kept in a file at configPath. The
+// file can't pick the device, the launch always targets `deviceId`.
+export const startScrcpyFromFile = async (
+  deviceId: string,
+  configPath: string,
+  callback: (line: string, sessionId: number) => void,
+  onClose: (data: ScrcpyExit) => void,
+  options: StartScrcpyOptions = {}
 ): Promise<Child> => {
-  const command = new Command("scrcpy" + _binaryExtension, args);
-  command.on("error", callback);
-  command.on('close', onClose);
-  command.stdout.on("data", callback);
-  command.stderr.on("data", callback);
+  const content = await invoke<string>("read_args_file", { path: configPath });
+  const fileArgs = parseArgsFile(content);
+  if (hasFlag(fileArgs, "-s") || hasFlag(fileArgs, "--serial")) {
+    throw new Error(`${configPath} must not select a device with -s/--serial`);
+  }
+  return startScrcpy(["-s", deviceId, ...fileArgs], callback, onClose, options);
+};
 
-  return command.spawn();
+export type LogcatFile = {
+  deviceId: string;
+  path: string;
+  finished: boolean;
+  // set on the finished event when adb itself ended the capture
+  error: string | null;
+};
+// Captures `adb logcat` of the device into dest, continuing in
+// "<name>.1.<ext>", "<name>.2.<ext>"... each time a file reaches maxBytes
+export const startLogcatToFile = (
+  deviceId: string,
+  dest: string,
+  maxBytes: number
+): Promise<void> => {
+  return invoke<void>("start_logcat_to_file", { deviceId, dest, maxBytes });
+};
+export const stopLogcatToFile = (deviceId: string): Promise<void> => {
+  return invoke<void>("stop_logcat_to_file", { deviceId });
+};
+// called with the file a capture writes to, and once more when it ends
+export const onLogcatFile = (
+  handler: (file: LogcatFile) => void
+): Promise<UnlistenFn> => {
+  return listen<LogcatFile>("logcat-file", (event) => handler(event.payload));
+};
+
+// screen_off_timeout is in milliseconds, Integer.MAX_VALUE is the "never"
+// that settings apps use
+const SCREEN_TIMEOUT_NEVER = 2147483647;
+// seconds before the screen turns off, 0 for never
+export const getScreenTimeout = async (deviceId: string): Promise<number> => {
+  const value = Number(
+    await readSetting(deviceId, "system", "screen_off_timeout")
+  );
+  if (!Number.isInteger(value)) {
+    throw new Error(`${deviceId} reported no screen timeout`);
+  }
+  return value >= SCREEN_TIMEOUT_NEVER ? 0 : Math.round(value / 1000);
+};
+// Keeps the screen on after the timeout independent of scrcpy's
+// --stay-awake, which only holds while mirroring over USB. 0 means never
+// turn off. Resolves with the timeout read back, in seconds.
+export const setScreenTimeout = async (
+  deviceId: string,
+  seconds: number
+): Promise<number> => {
+  if (!Number.isInteger(seconds) || seconds < 0) {
+    throw new Error(`Invalid screen timeout: ${seconds}`);
+  }
+  const milliseconds = seconds
+    ? Math.min(seconds * 1000, SCREEN_TIMEOUT_NEVER)
+    : SCREEN_TIMEOUT_NEVER;
+  const { stdout, stderr } = await runAdbShell(deviceId, [
+    "settings",
+    "put",
+    "system",
+    "screen_off_timeout",
+    milliseconds.toString(),
+  ]);
+  if (isPermissionDenied(stdout + stderr)) {
+    throw new Error(
+      `The device refused to change screen_off_timeout, it requires a permission adb shell doesn't have on this device`
+    );
+  }
+  return getScreenTimeout(deviceId);
+};
+// `svc power stayon`, keeps the screen on while charging over "usb", "ac"
+// or "wireless", or on any of them with true
+export const setStayOn = async (
+  deviceId: string,
+  mode: boolean | "usb" | "ac" | "wireless"
+): Promise<void> => {
+  await executeAdbChecked([
+    "-s",
+    deviceId,
+    "shell",
+    "svc",
+    "power",
+    "stayon",
+    mode.toString(),
+  ]);
+};
+
+export type MdnsDevice = {
+  // service instance name, e.g. "adb-R3CN30XXXX-a1b2c3"
+  name: string;
+  host: string;
+  port: number;
+};
+export type MdnsDeviceList = {
+  devices: MdnsDevice[];
+  // why the list may be empty, e.g. an adb without mDNS support
+  message?: string;
+};
+// Paired devices advertising wireless debugging, connect to one with
+// `adb connect host:port`. Parses lines such as
+// "adb-R3CN30XXXX-a1b2c3	_adb-tls-connect._tcp	192.168.1.23:37125"
+export const listMdnsDevices = async (): Promise<MdnsDeviceList> => {
+  const { stdout, stderr, code } = await executeAdb(["mdns", "services"]);
+  const devices: MdnsDevice[] = [];
+  for (const line of stdout.split("\n")) {
+    const [name, service, address] = line.trim().split(/\s+/);
+    if (!address || !/^_adb-tls-connect\._tcp\.?$/.test(service)) {
+      continue;
+    }
+    const separator = address.lastIndexOf(":");
+    const port = Number(address.slice(separator + 1));
+    if (separator <= 0 || !Number.isInteger(port)) {
+      continue;
+    }
+    devices.push({ name, host: address.slice(0, separator), port });
+  }
+  if (devices.length) {
+    return { devices };
+  }
+  if (code !== 0 || /unknown|not supported|disabled/i.test(stderr)) {
+    return {
+      devices,
+      message: `mDNS discovery is unavailable with this adb: ${(stderr || stdout).trim()}`,
+    };
+  }
+  return {
+    devices,
+    message:
+      "No devices found, enable wireless debugging and pair the device first",
+  };
+};
+
+// running bugreports by device serial
+const bugreports = new Map<string, { child?: Child; cancelled: boolean }>();
+// Writes a bugreport zip to dest, which takes minutes. onProgress gets adb's
+// progress output such as "[ 42%] generating bugreport-...zip". Resolves with
+// dest, rejects when adb fails or cancelBugreport stopped it.
+export const captureBugreport = async (
+  deviceId: string,
+  dest: string,
+  onProgress?: (line: string) => void
+): Promise<string> => {
+  if (!dest.toLowerCase().endsWith(".zip")) {
+    throw new Error(`Bugreport destination must be a .zip file: ${dest}`);
+  }
+  if (bugreports.has(deviceId)) {
+    throw new Error(`A bugreport of ${deviceId} is already running`);
+  }
+  const report: { child?: Child; cancelled: boolean } = { cancelled: false };
+  bugreports.set(deviceId, report);
+  const command = await adbCommand([
+    "-s",
+    deviceId,
+    "bugreport",
+    dest,
+  ]);
+  let lastOutput = "";
+  const onLine = (line: string): void => {
+    // progress is redrawn with carriage returns
+    for (const part of line.split("\r")) {
+      if (part.trim()) {
+        lastOutput = part.trim();
+        onProgress?.(lastOutput);
+      }
+    }
+  };
+  command.stdout.on("data", onLine);
+  command.stderr.on("data", onLine);
+  const finished = new Promise<string>((resolve, reject) => {
+    const fail = (reason: string): void => {
+      reject(
+        new Error(
+          report.cancelled
+            ? `Bugreport of ${deviceId} was cancelled`
+            : `Bugreport of ${deviceId} failed: ${reason}`
+        )
+      );
+    };
+    command.on("close", ({ code }) => {
+      bugreports.delete(deviceId);
+      if (code === 0 && !report.cancelled) {
+        resolve(dest);
+      } else {
+        fail(lastOutput);
+      }
+    });
+    command.on("error", (error) => {
+      bugreports.delete(deviceId);
+      fail(String(error));
+    });
+  });
+  try {
+    report.child = await command.spawn();
+  } catch (error) {
+    bugreports.delete(deviceId);
+    throw error;
+  }
+  if (report.cancelled) {
+    await report.child.kill();
+  }
+  return finished;
+};
+export const cancelBugreport = async (deviceId: string): Promise<void> => {
+  const report = bugreports.get(deviceId);
+  if (report && !report.cancelled) {
+    report.cancelled = true;
+    await report.child?.kill();
+  }
+};
+
+const readProp = async (deviceId: string, name: string): Promise<string> => {
+  const { stdout } = await runAdbShell(deviceId, ["getprop", name]);
+  return stdout.trim();
+};
+// BCP 47 language tag, e.g. "fr-FR", "zh-Hant-TW" or "es-419"
+const LOCALE_PATTERN = /^[a-z]{2,3}(-[A-Z][a-z]{3})?(-([A-Z]{2}|\d{3}))?$/;
+// "Europe/Paris", "America/Argentina/Buenos_Aires" or "UTC"
+const TIMEZONE_PATTERN = /^[A-Za-z][A-Za-z0-9_+-]*(\/[A-Za-z0-9_+-]+)*$/;
+
+export const getDeviceLocale = async (deviceId: string): Promise<string> => {
+  return (
+    (await readProp(deviceId, "persist.sys.locale")) ||
+    (await readProp(deviceId, "ro.product.locale"))
+  );
+};
+// persist.sys.locale can only be written as root on user builds, the new
+// locale applies once the device reboots. Resolves with the stored locale,
+// rejects when the device kept the old one.
+export const setDeviceLocale = async (
+  deviceId: string,
+  locale: string
+): Promise<string> => {
+  if (!LOCALE_PATTERN.test(locale)) {
+    throw new Error(`Invalid locale ${locale}, expected a tag such as fr-FR`);
+  }
+  await runAdbShell(deviceId, ["setprop", "persist.sys.locale", locale]);
+  const applied = await getDeviceLocale(deviceId);
+  if (applied !== locale) {
+    throw new Error(
+      `${deviceId} kept locale ${applied || "unknown"}, changing it requires root on this device`
+    );
+  }
+  return applied;
+};
+
+export const getDeviceTimezone = async (deviceId: string): Promise<string> => {
+  return readProp(deviceId, "persist.sys.timezone");
+};
+// AlarmManager's setTimeZone is reachable from the shell without root,
+// automatic time zone is turned off first or the network would override it.
+// Resolves with the time zone read back.
+export const setDeviceTimezone = async (
+  deviceId: string,
+  timezone: string
+): Promise<string> => {
+  if (!TIMEZONE_PATTERN.test(timezone)) {
+    throw new Error(
+      `Invalid time zone ${timezone}, expected e.g. Europe/Paris`
+    );
+  }
+  await runAdbShell(deviceId, [
+    "settings",
+    "put",
+    "global",
+    "auto_time_zone",
+    "0",
+  ]);
+  await runAdbShell(deviceId, [
+    "service",
+    "call",
+    "alarm",
+    "3",
+    "s16",
+    timezone,
+  ]);
+  let applied = await getDeviceTimezone(deviceId);
+  if (applied !== timezone) {
+    // the transaction code of setTimeZone differs on some releases
+    await runAdbShell(deviceId, ["setprop", "persist.sys.timezone", timezone]);
+    applied = await getDeviceTimezone(deviceId);
+  }
+  if (applied !== timezone) {
+    throw new Error(
+      `${deviceId} kept time zone ${applied || "unknown"}, changing it requires root on this device`
+    );
+  }
+  return applied;
+};
+
+export type DeviceProcess = { pid: number; name: string };
+// Rows of a ps listing whose header has PID and NAME columns, NAME is last.
+// The old toolbox ps has an unlabeled state column before NAME, so the name
+// is taken from the last field rather than the NAME position.
+export const parsePsOutput = (output: string): DeviceProcess[] => {
+  const [header, ...rows] = output.trim().split("\n");
+  const columns = (header || "").trim().split(/\s+/);
+  const pidColumn = columns.indexOf("PID");
+  if (pidColumn === -1 || columns[columns.length - 1] !== "NAME") {
+    return [];
+  }
+  return rows
+    .map((row) => row.trim().split(/\s+/))
+    .filter((fields) => fields.length >= columns.length)
+    .map((fields) => ({
+      pid: Number(fields[pidColumn]),
+      name: fields[fields.length - 1],
+    }))
+    .filter(({ pid }) => Number.isInteger(pid));
+};
+// toybox ps (Android 8+) needs -A for other users' processes, the old
+// toolbox ps lists everything and knows neither -A nor -o
+export const listDeviceProcesses = async (
+  deviceId: string
+): Promise<DeviceProcess[]> => {
+  const { stdout } = await runAdbShell(deviceId, [
+    "ps",
+    "-A",
+    "-o",
+    "PID,NAME",
+  ]);
+  const processes = parsePsOutput(stdout);
+  if (processes.length) {
+    return processes;
+  }
+  return parsePsOutput((await runAdbShell(deviceId, ["ps"])).stdout);
+};
+const PACKAGE_NAME_PATTERN = /^[a-zA-Z]\w*(\.[a-zA-Z]\w*)+$/;
+// `am force-stop`, for an app under test that stopped responding
+export const forceStopPackage = async (
+  deviceId: string,
+  packageName: string
+): Promise<void> => {
+  if (!PACKAGE_NAME_PATTERN.test(packageName)) {
+    throw new Error(`Invalid package name: ${packageName}`);
+  }
+  await executeAdbChecked([
+    "-s",
+    deviceId,
+    "shell",
+    "am",
+    "force-stop",
+    packageName,
+  ]);
+};
+
+export type VideoCodecSupport = Record<"h264" | "h265" | "av1", boolean>;
+// "    --video-codec=h265 --video-encoder=c2.qti.hevc.encoder"
+const VIDEO_ENCODER_LINE = /--video-codec=(\w+) --video-encoder=\S+/g;
+// Which --video-codec values the device has an encoder for, according to
+// scrcpy's --list-encoders (scrcpy 2.0+). Software encoders count.
+export const getDeviceCodecSupport = async (
+  deviceId: string
+): Promise<VideoCodecSupport> => {
+  const { stdout, stderr } = await executeScrcpy(
+    ["-s", deviceId, "--list-encoders"],
+    await scrcpyEnv()
+  );
+  const output = stdout + stderr;
+  const support: VideoCodecSupport = { h264: false, h265: false, av1: false };
+  let found = false;
+  for (const [, codec] of output.matchAll(VIDEO_ENCODER_LINE)) {
+    found = true;
+    if (codec in support) {
+      support[codec as keyof VideoCodecSupport] = true;
+    }
+  }
+  if (!found) {
+    const [, error] = output.match(/ERROR: (.+)/) || [];
+    throw new Error(
+      /unrecognized option|unknown option/i.test(output)
+        ? "This scrcpy version can't list encoders, it requires 2.0+"
+        : `No video encoders found for ${deviceId}${error ? `: ${error.trim()}` : ""}`
+    );
+  }
+  return support;
+};
+
+// host clipboard, for the copy buttons next to serials, addresses and
+// commands. Resolves with false instead of rejecting.
+export const copyToClipboard = async (text: string): Promise<boolean> => {
+  try {
+    await writeText(text);
+    return true;
+  } catch {
+    return false;
+  }
+};
+
+const assertWirelessAddress = (address: string): void => {
+  if (!isWirelessSerial(address)) {
+    throw new Error(`Invalid address ${address}, expected host:port`);
+  }
+};
+// `adb connect`, exits 0 even when it failed so its output decides
+export const adbConnect = async (address: string): Promise<void> => {
+  assertWirelessAddress(address);
+  const { stdout, stderr } = await executeAdb(["connect", address]);
+  const output = (stdout + stderr).trim();
+  if (!isConnectedOutput(output)) {
+    throw new Error(output || `Failed to connect to ${address}`);
+  }
+  invalidateDeviceList();
+};
+export const adbDisconnect = async (address: string): Promise<void> => {
+  assertWirelessAddress(address);
+  const { stdout, stderr, code } = await executeAdb(["disconnect", address]);
+  const output = (stdout + stderr).trim();
+  if (code !== 0 || /error|no such device/i.test(output)) {
+    throw new Error(output || `Failed to disconnect ${address}`);
+  }
+  invalidateDeviceList();
+};
+
+export type PairResult = { paired: boolean; output: string };
+// Android 11+ wireless debugging: pairs with the host:port and 6-digit code
+// shown under "Pair device with pairing code", then adbConnect the address
+// listed under "Wireless debugging". The code never ends up in logs or errors.
+export const adbPair = async (
+  address: string,
+  code: string
+): Promise<PairResult> => {
+  assertWirelessAddress(address);
+  if (!/^\d{6}$/.test(code)) {
+    throw new Error("The pairing code must be 6 digits");
+  }
+  const { stdout, stderr } = await executeAdb(["pair", address, code]);
+  const output = (stdout + stderr).split(code).join("******").trim();
+  return { paired: /Successfully paired/i.test(output), output };
 };
@@ -0,0 +1,81 @@
+import { register, unregister } from "@tauri-apps/api/globalShortcut";
+
+import { stopAllHotkey } from "./storage";
+
+const HOTKEY_MODIFIERS = [
+  "commandorcontrol",
+  "cmdorctrl",
+  "command",
+  "cmd",
+  "control",
+  "ctrl",
+  "alt",
+  "option",
+  "altgr",
+  "shift",
+  "super",
+  "meta",
+];
+const HOTKEY_KEYS =
+  /^([a-z0-9]|f([1-9]|1\d|2[0-4])|escape|esc|space|tab|enter|return|backspace|delete|insert|home|end|pageup|pagedown|up|down|left|right|plus|minus)$/i;
+// "Modifier+...+Key", a global shortcut without a modifier would swallow
+// the key in every other application
+export const isValidAccelerator = (accelerator: string): boolean => {
+  const parts = accelerator.split("+").map((part) => part.trim());
+  const key = parts.pop() || "";
+  return (
+    parts.length > 0 &&
+    parts.every((part) => HOTKEY_MODIFIERS.includes(part.toLowerCase())) &&
+    new Set(parts.map((part) => part.toLowerCase())).size === parts.length &&
+    HOTKEY_KEYS.test(key)
+  );
+};
+
+let registered: { accelerator: string; handler: () => void } | undefined;
+const registerAccelerator = async (
+  accelerator: string,
+  handler: () => void
+): Promise<void> => {
+  try {
+    await register(accelerator, handler);
+  } catch (error) {
+    throw new Error(
+      `Could not register the stop all hotkey ${accelerator}, another application or the OS may already use it: ${error}`
+    );
+  }
+  registered = { accelerator, handler };
+};
+
+export const registerStopAllHotkey = async (
+  handler: () => void
+): Promise<void> => {
+  await unregisterStopAllHotkey();
+  await registerAccelerator(stopAllHotkey.value, handler);
+};
+export const unregisterStopAllHotkey = async (): Promise<void> => {
+  if (registered) {
+    const { accelerator } = registered;
+    registered = undefined;
+    await unregister(accelerator);
+  }
+};
+// Rebinds the registered hotkey and persists the accelerator. When the new
+// one can't be registered the previous binding is kept.
+export const setStopAllHotkey = async (accelerator: string): Promise<void> => {
+  if (!isValidAccelerator(accelerator)) {
+    throw new Error(
+      `Invalid hotkey ${accelerator}, expected modifiers and a key such as ${stopAllHotkey.value}`
+    );
+  }
+  const previous = registered;
+  if (previous) {
+    await unregisterStopAllHotkey();
+    try {
+      await registerAccelerator(accelerator, previous.handler);
+    } catch (error) {
+      await registerAccelerator(previous.accelerator, previous.handler);
+      throw error;
+    }
+  }
+  stopAllHotkey.value = accelerator;
+};
@@ -0,0 +1,40 @@
+import { afterEach, beforeEach, describe, expect, it, vi } from "vitest";
+
+import { listVisibleDevices } from "./commands";
+import { startDeviceMonitor, stopDeviceMonitor } from "./monitor";
+import { monitorInterval } from "./storage";
+
+vi.mock("./commands", () => ({
+  listVisibleDevices: vi.fn(async () => []),
+  onDeviceListInvalidated: vi.fn(() => () => {}),
+}));
+
+describe("device monitor", () => {
+  beforeEach(() => {
+    vi.useFakeTimers();
+    vi.mocked(listVisibleDevices).mockClear();
+  });
+  afterEach(() => {
+    stopDeviceMonitor();
+    vi.useRealTimers();
+  });
+  const broken = (): never => {
+    throw new Error("broken handler");
+  };
+  it("keeps polling after onChange throws", async () => {
+    const onError = vi.fn();
+    startDeviceMonitor(broken, onError);
+    await vi.advanceTimersByTimeAsync(monitorInterval.value);
+    expect(listVisibleDevices).toHaveBeenCalledTimes(2);
+    expect(onError).toHaveBeenCalledWith(
+      "device monitor handler failed: Error: broken handler"
+    );
+  });
+  it("keeps polling after onHeartbeat throws", async () => {
+    const onError = vi.fn();
+    startDeviceMonitor(() => {}, onError, broken);
+    await vi.advanceTimersByTimeAsync(monitorInterval.value * 2);
+    expect(listVisibleDevices).toHaveBeenCalledTimes(3);
+    expect(onError).toHaveBeenCalledTimes(3);
+  });
+});
@@ -0,0 +1,188 @@
+import {
+  DeviceInfo,
+  listVisibleDevices,
+  onDeviceListInvalidated,
+} from "./commands";
+import { watch } from "vue";
+
+import {
+  MONITOR_INTERVAL_MAX,
+  MONITOR_INTERVAL_MIN,
+  isDeviceAllowed,
+  monitorInterval,
+} from "./storage";
+
+// after this many failed polls in a row the interval doubles up to
+// MONITOR_INTERVAL_MAX, the longest interval the setting allows
+const MONITOR_BACKOFF_AFTER = 3;
+// an unchanged error is only reported again every this many failures
+const MONITOR_REPEAT_REPORT = 10;
+
+export type DeviceMonitorHandler = (
+  devices: DeviceInfo[],
+  added: string[],
+  removed: string[]
+) => void;
+
+export type MonitorHeartbeat = {
+  // epoch milliseconds of the poll
+  at: number;
+  // undefined when the poll failed
+  deviceCount?: number;
+  // failed polls in a row
+  failures: number;
+};
+
+// bumped on every start/stop so a poll still in flight from a previous run
+// never schedules another one
+let monitorGeneration = 0;
+let monitorTimer: ReturnType<typeof setTimeout> | undefined;
+let lastHeartbeat: MonitorHeartbeat | undefined;
+let monitoring = false;
+let unsubscribeInvalidation: (() => void) | undefined;
+let unwatchInterval: (() => void) | undefined;
+export const isMonitoring = (): boolean => {
+  return monitoring;
+};
+// a heartbeat much older than the poll interval means the loop died
+export const getMonitorHeartbeat = (): MonitorHeartbeat | undefined => {
+  return lastHeartbeat;
+};
+
+// Polls `adb devices` until stopped. Devices already connected on the first
+// poll are reported in `devices` only, never as `added`. A persisting error
+// is reported once, then summarized while the polls back off.
+export const startDeviceMonitor = (
+  onChange: DeviceMonitorHandler,
+  onError: (error: unknown) => void,
+  onHeartbeat?: (heartbeat: MonitorHeartbeat) => void
+): void => {
+  stopDeviceMonitor();
+  monitoring = true;
+  const generation = monitorGeneration;
+  let previous: string[] | undefined;
+  let failures = 0;
+  let lastError: string | undefined;
+  // when the pending poll was scheduled
+  let scheduledAt: number | undefined;
+  // the list was invalidated while a poll was in flight, its result may
+  // already be stale
+  let repollPending = false;
+  const nextInterval = (): number => {
+    // read on every poll so a changed setting applies to the next one
+    const interval = Math.min(
+      Math.max(monitorInterval.value, MONITOR_INTERVAL_MIN),
+      MONITOR_INTERVAL_MAX
+    );
+    if (failures < MONITOR_BACKOFF_AFTER) {
+      return interval;
+    }
+    const exponent = failures - MONITOR_BACKOFF_AFTER + 1;
+    return Math.min(interval * 2 ** exponent, MONITOR_INTERVAL_MAX);
+  };
+  // a throwing handler must neither count as an adb failure nor end the loop
+  // by skipping the next setTimeout, it is reported through onError instead
+  const notify = (handler: () => void): void => {
+    try {
+      handler();
+    } catch (error) {
+      try {
+        onError(`device monitor handler failed: ${error}`);
+      } catch {
+        // onError itself threw, there is nowhere left to report to
+      }
+    }
+  };
+  const beat = (deviceCount?: number): void => {
+    lastHeartbeat = { at: Date.now(), deviceCount, failures };
+    notify(() => onHeartbeat?.(lastHeartbeat as MonitorHeartbeat));
+  };
+  const poll = async (): Promise<void> => {
+    monitorTimer = undefined;
+    scheduledAt = undefined;
+    let devices: DeviceInfo[] | undefined;
+    let failure: unknown;
+    try {
+      devices = await listVisibleDevices();
+    } catch (error) {
+      failure = error;
+    }
+    if (generation !== monitorGeneration) {
+      return;
+    }
+    if (devices) {
+      const serials = devices.map((device) => device.serial);
+      // every device is tracked, only allowed ones are reported as changes
+      const added = previous
+        ? serials.filter(
+            (serial) => !previous?.includes(serial) && isDeviceAllowed(serial)
+          )
+        : [];
+      const removed = (previous || []).filter(
+        (serial) => !serials.includes(serial) && isDeviceAllowed(serial)
+      );
+      previous = serials;
+      failures = 0;
+      lastError = undefined;
+      beat(devices.length);
+      notify(() => onChange(devices as DeviceInfo[], added, removed));
+    } else {
+      failures++;
+      beat();
+      const message = String(failure);
+      if (message !== lastError) {
+        lastError = message;
+        notify(() => onError(failure));
+      } else if (failures % MONITOR_REPEAT_REPORT === 0) {
+        notify(() =>
+          onError(`still failing after ${failures} attempts: ${message}`)
+        );
+      }
+    }
+    if (generation === monitorGeneration) {
+      scheduledAt = Date.now();
+      monitorTimer = setTimeout(poll, repollPending ? 0 : nextInterval());
+      repollPending = false;
+    }
+  };
+  // a lowered interval shortens the pending wait instead of letting it run
+  // out at the old length
+  unwatchInterval = watch(monitorInterval, (interval, previous) => {
+    if (
+      interval < previous &&
+      monitorTimer !== undefined &&
+      scheduledAt !== undefined &&
+      generation === monitorGeneration
+    ) {
+      clearTimeout(monitorTimer);
+      const remaining = scheduledAt + nextInterval() - Date.now();
+      monitorTimer = setTimeout(poll, Math.max(0, remaining));
+    }
+  });
+  // poll right away instead of waiting out the interval. A poll already
+  // running polls again as soon as it is done.
+  unsubscribeInvalidation = onDeviceListInvalidated(() => {
+    if (generation !== monitorGeneration) {
+      return;
+    }
+    if (monitorTimer === undefined) {
+      repollPending = true;
+      return;
+    }
+    clearTimeout(monitorTimer);
+    monitorTimer = undefined;
+    poll();
+  });
+  poll();
+};
+export const stopDeviceMonitor = (): void => {
+  monitorGeneration++;
+  monitoring = false;
+  clearTimeout(monitorTimer);
+  monitorTimer = undefined;
+  unsubscribeInvalidation?.();
+  unsubscribeInvalidation = undefined;
+  unwatchInterval?.();
+  unwatchInterval = undefined;
+  lastHeartbeat = undefined;
+};
@@ -0,0 +1,36 @@
+import { describe, expect, it } from "vitest";
+
+import { isEmulatorSerial, isWirelessSerial } from "./serial";
+
+describe("isWirelessSerial", () => {
+  it.each([
+    "192.168.1.5:5555",
+    "[fe80::1]:5555",
+    "phone.lan:5555",
+    "adb-R3CN30XXXX-a1b2c3._adb-tls-connect._tcp",
+  ])("accepts %s", (serial) => {
+    expect(isWirelessSerial(serial)).toBe(true);
+  });
+  it.each([
+    "emulator-5554",
+    "R3CN30XXXX",
+    "192.168.1.5:0",
+    "192.168.1.5:65536",
+    "192.168.1.5:adb",
+    ":5555",
+  ])("rejects %s", (serial) => {
+    expect(isWirelessSerial(serial)).toBe(false);
+  });
+});
+
+describe("isEmulatorSerial", () => {
+  it("accepts emulator-5554", () => {
+    expect(isEmulatorSerial("emulator-5554")).toBe(true);
+  });
+  it.each(["R3CN30XXXX", "192.168.1.5:5555", "emulator-"])(
+    "rejects %s",
+    (serial) => {
+      expect(isEmulatorSerial(serial)).toBe(false);
+    }
+  );
+});
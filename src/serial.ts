@@ -0,0 +1,25 @@
+// Serial classification, free of Tauri imports so it runs under plain node
+export const isEmulatorSerial = (serial: string): boolean => {
+  return /^emulator-\d+$/.test(serial);
+};
+// "192.168.1.5:5555", "[fe80::1]:5555", "phone.lan:5555" or an mDNS service
+// name such as "adb-R3CN30XXXX-a1b2c3._adb-tls-connect._tcp"
+export const isWirelessSerial = (serial: string): boolean => {
+  if (/\._adb-tls-connect\._tcp\.?$/.test(serial)) {
+    return true;
+  }
+  const separator = serial.lastIndexOf(":");
+  if (separator <= 0) {
+    return false;
+  }
+  const host = serial.slice(0, separator);
+  const port = Number(serial.slice(separator + 1));
+  if (!Number.isInteger(port) || port <= 0 || port > 65535) {
+    return false;
+  }
+  return (
+    /^\[[0-9a-f:.]+\]$/i.test(host) ||
+    /^(\d{1,3}\.){3}\d{1,3}$/.test(host) ||
+    /^[a-z0-9]([a-z0-9-]*[a-z0-9])?(\.[a-z0-9]([a-z0-9-]*[a-z0-9])?)*$/i.test(host)
+  );
+};
@@ -0,0 +1,76 @@
+import { compareVersions, getScrcpyVersion } from "./commands";
+
+export type ScrcpyShortcut = {
+  // "MOD" is the --shortcut-mod key, left Alt or left Super by default
+  keys: string;
+  action: string;
+};
+type ShortcutEntry = ScrcpyShortcut & {
+  // first scrcpy version with the shortcut
+  since?: string;
+  // first version without it, when scrcpy dropped or rebound it
+  until?: string;
+};
+
+// scrcpy doesn't report its shortcuts, keep this in sync with the
+// "Shortcuts" section of its documentation
+const SCRCPY_SHORTCUTS: ShortcutEntry[] = [
+  { keys: "MOD+f", action: "Toggle fullscreen" },
+  { keys: "MOD+Left / MOD+Right", action: "Rotate display left / right" },
+  { keys: "MOD+g", action: "Resize window to 1:1 (pixel-perfect)" },
+  {
+    keys: "MOD+w / double-click on black border",
+    action: "Resize window to remove black borders",
+  },
+  { keys: "MOD+h / middle-click", action: "Click on HOME" },
+  { keys: "MOD+b / MOD+Backspace / right-click", action: "Click on BACK" },
+  { keys: "MOD+s / 4th-click", action: "Click on APP_SWITCH" },
+  { keys: "MOD+m", action: "Click on MENU (unlock screen)" },
+  { keys: "MOD+Up / MOD+Down", action: "Click on VOLUME_UP / VOLUME_DOWN" },
+  { keys: "MOD+p", action: "Click on POWER (turn screen on/off)" },
+  { keys: "Right-click (screen off)", action: "Power on" },
+  { keys: "MOD+o", action: "Turn device screen off (keep mirroring)" },
+  { keys: "MOD+Shift+o", action: "Turn device screen on" },
+  { keys: "MOD+r", action: "Rotate device screen" },
+  {
+    keys: "MOD+n / MOD+n+n",
+    action: "Expand notification panel / settings panel",
+  },
+  { keys: "MOD+Shift+n", action: "Collapse notification panel" },
+  { keys: "MOD+c", action: "Copy to clipboard" },
+  { keys: "MOD+x", action: "Cut to clipboard" },
+  { keys: "MOD+v", action: "Synchronize clipboards and paste" },
+  {
+    keys: "MOD+Shift+v",
+    action: "Inject computer clipboard text as key events",
+  },
+  {
+    keys: "MOD+k",
+    action: "Open keyboard settings on the device (UHID keyboard only)",
+    since: "2.4",
+  },
+  { keys: "MOD+i", action: "Enable/disable FPS counter (on stdout)" },
+  { keys: "Ctrl+click-and-move", action: "Pinch-to-zoom and rotate" },
+  { keys: "Drag & drop APK file", action: "Install APK from computer" },
+  {
+    keys: "Drag & drop non-APK file",
+    action: "Push file to device",
+    since: "1.11",
+  },
+];
+
+// the shortcuts of the given scrcpy version, or every known shortcut when
+// the version is unknown
+export const scrcpyShortcutsFor = (version?: string): ScrcpyShortcut[] => {
+  return SCRCPY_SHORTCUTS.filter(
+    ({ since, until }) =>
+      !version ||
+      ((!since || compareVersions(version, since) >= 0) &&
+        (!until || compareVersions(version, until) < 0))
+  ).map(({ keys, action }) => ({ keys, action }));
+};
+// for the in-app cheat sheet, matched to the installed scrcpy
+export const getScrcpyShortcuts = async (): Promise<ScrcpyShortcut[]> => {
+  const version = await getScrcpyVersion().catch(() => undefined);
+  return scrcpyShortcutsFor(version);
+};
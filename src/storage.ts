@@ -0,0 +1,372 @@
+import { useStorage } from "@vueuse/core";
+
+// device serial -> args of the last successful launch, wireless serials such
+// as "192.168.1.5:5555" are plain JSON object keys so they need no escaping
+export const deviceSettings = useStorage<Record<string, string[]>>(
+  "deviceSettings",
+  {},
+  undefined,
+  {
+    mergeDefaults: true,
+  }
+);
+
+export const getDeviceSettings = (deviceId: string): string[] | undefined => {
+  return deviceSettings.value[deviceId];
+};
+export const saveDeviceSettings = (deviceId: string, args: string[]): void => {
+  deviceSettings.value = { ...deviceSettings.value, [deviceId]: [...args] };
+};
+
+const DEVICE_ALIAS_MAX_LENGTH = 64;
+const DEVICE_TAG_MAX_LENGTH = 32;
+export type DeviceMetadata = {
+  // user defined label such as "Pixel 7 - QA"
+  alias?: string;
+  // CSS hex color, e.g. "#f5222d"
+  color?: string;
+  // free form group such as "prod" or "staging"
+  tag?: string;
+};
+export const deviceMetadata = useStorage<Record<string, DeviceMetadata>>(
+  "deviceMetadata",
+  {},
+  undefined,
+  {
+    mergeDefaults: true,
+  }
+);
+// aliases were stored on their own before colors and tags existed
+const legacyAliases = localStorage.getItem("deviceAliases");
+if (legacyAliases) {
+  try {
+    const aliases: Record<string, string> = JSON.parse(legacyAliases);
+    const metadata = { ...deviceMetadata.value };
+    for (const [serial, alias] of Object.entries(aliases)) {
+      metadata[serial] = { alias, ...metadata[serial] };
+    }
+    deviceMetadata.value = metadata;
+  } catch {
+    // unreadable legacy data, nothing worth keeping
+  }
+  localStorage.removeItem("deviceAliases");
+}
+
+export const getDeviceMetadata = (serial: string): DeviceMetadata => {
+  return deviceMetadata.value[serial] || {};
+};
+export const getDeviceAlias = (serial: string): string | undefined => {
+  return getDeviceMetadata(serial).alias;
+};
+// Fields left undefined keep their stored value, null or an empty string
+// clears them. Throws on a color that is not a CSS hex color.
+export const setDeviceMetadata = (
+  serial: string,
+  changes: { [Key in keyof DeviceMetadata]?: string | null }
+): void => {
+  const current: DeviceMetadata = { ...getDeviceMetadata(serial) };
+  const normalize = (value: string | null, maxLength: number) => {
+    return value?.trim().slice(0, maxLength) || undefined;
+  };
+  if (changes.alias !== undefined) {
+    current.alias = normalize(changes.alias, DEVICE_ALIAS_MAX_LENGTH);
+  }
+  if (changes.tag !== undefined) {
+    current.tag = normalize(changes.tag, DEVICE_TAG_MAX_LENGTH);
+  }
+  if (changes.color !== undefined) {
+    const color = normalize(changes.color, 7);
+    if (color && !/^#([0-9a-f]{3}|[0-9a-f]{6})$/i.test(color)) {
+      throw new Error(`Invalid color: ${changes.color}`);
+    }
+    current.color = color;
+  }
+  const metadata = { ...deviceMetadata.value };
+  if (current.alias || current.color || current.tag) {
+    metadata[serial] = current;
+  } else {
+    delete metadata[serial];
+  }
+  deviceMetadata.value = metadata;
+};
+// an empty or missing alias removes the label of the device
+export const setDeviceAlias = (serial: string, alias?: string | null): void => {
+  setDeviceMetadata(serial, { alias: alias || null });
+};
+
+// launch scrcpy with the adb sitting next to its binary instead of the one
+// scrcpy would otherwise pick up
+export const preferBundledAdb = useStorage<boolean>(
+  "preferBundledAdb",
+  false,
+  undefined,
+  {
+    mergeDefaults: true,
+  }
+);
+
+// the opposite: launch scrcpy with the adb on PATH, e.g. an Android SDK adb,
+// instead of the one shipped next to scrcpy. Ignored while preferBundledAdb
+// is set.
+export const preferSystemAdb = useStorage<boolean>(
+  "preferSystemAdb",
+  false,
+  undefined,
+  {
+    mergeDefaults: true,
+  }
+);
+
+// start scrcpy as soon as the device monitor sees a new device, using the
+// device's last launch args or the current configuration
+export const autoLaunch = useStorage<boolean>("autoLaunch", false, undefined, {
+  mergeDefaults: true,
+});
+
+export type SavedSession = { deviceId: string; args: string[] };
+// relaunch the mirrors that were open when the app was last closed
+export const restoreSession = useStorage<boolean>(
+  "restoreSession",
+  false,
+  undefined,
+  {
+    mergeDefaults: true,
+  }
+);
+export const savedSessions = useStorage<SavedSession[]>(
+  "savedSessions",
+  [],
+  undefined,
+  {
+    mergeDefaults: true,
+  }
+);
+
+// scrcpy-server jar handed to scrcpy through SCRCPY_SERVER_PATH, for installs
+// where the jar doesn't sit next to the scrcpy binary
+export const scrcpyServerPath = useStorage<string>(
+  "scrcpyServerPath",
+  "",
+  undefined,
+  {
+    mergeDefaults: true,
+  }
+);
+
+export const SCRCPY_VERBOSITY_LEVELS = [
+  "verbose",
+  "debug",
+  "info",
+  "warn",
+  "error",
+] as const;
+export type ScrcpyVerbosity = (typeof SCRCPY_VERBOSITY_LEVELS)[number];
+// passed as --verbosity to every launch that doesn't set its own
+export const scrcpyVerbosity = useStorage<ScrcpyVerbosity>(
+  "scrcpyVerbosity",
+  "info",
+  undefined,
+  {
+    mergeDefaults: true,
+  }
+);
+export const setScrcpyVerbosity = (level: string): void => {
+  if (!SCRCPY_VERBOSITY_LEVELS.includes(level as ScrcpyVerbosity)) {
+    throw new Error(
+      `Invalid verbosity ${level}, expected one of ${SCRCPY_VERBOSITY_LEVELS.join(", ")}`
+    );
+  }
+  scrcpyVerbosity.value = level as ScrcpyVerbosity;
+};
+
+// hold launches until the device is unlocked, e.g. to not mirror a lock
+// screen during demos
+export const waitForUnlock = useStorage<boolean>(
+  "waitForUnlock",
+  false,
+  undefined,
+  {
+    mergeDefaults: true,
+  }
+);
+
+// reconnect and relaunch a wireless mirror that lost its device, e.g. after
+// a Wi-Fi hiccup, a few times in a row at most
+export const autoReconnectWireless = useStorage<boolean>(
+  "autoReconnectWireless",
+  false,
+  undefined,
+  {
+    mergeDefaults: true,
+  }
+);
+
+// relaunch once when scrcpy fails to start with a known transient error
+export const autoRetryLaunch = useStorage<boolean>(
+  "autoRetryLaunch",
+  false,
+  undefined,
+  {
+    mergeDefaults: true,
+  }
+);
+
+// show a phone connected over both USB and Wi-Fi as one entry, keyed on its
+// ro.serialno, instead of one entry per adb transport
+export const collapseTransports = useStorage<boolean>(
+  "collapseTransports",
+  false,
+  undefined,
+  {
+    mergeDefaults: true,
+  }
+);
+
+// global shortcut that stops every mirror, even when the window isn't focused
+export const DEFAULT_STOP_ALL_HOTKEY = "CommandOrControl+Shift+F12";
+export const stopAllHotkey = useStorage<string>(
+  "stopAllHotkey",
+  DEFAULT_STOP_ALL_HOTKEY,
+  undefined,
+  {
+    mergeDefaults: true,
+  }
+);
+
+// --window-title for launches that don't set one, e.g. "QA - {alias}".
+// {serial}, {alias} and {model} are replaced, empty disables it.
+export const windowTitleTemplate = useStorage<string>(
+  "windowTitleTemplate",
+  "",
+  undefined,
+  {
+    mergeDefaults: true,
+  }
+);
+
+// log lines waiting for the log view, older ones are dropped past this
+export const logQueueSize = useStorage<number>(
+  "logQueueSize",
+  1000,
+  undefined,
+  {
+    mergeDefaults: true,
+  }
+);
+
+export type DeviceFilterMode = "off" | "allow" | "deny";
+export type DeviceFilter = { mode: DeviceFilterMode; serials: string[] };
+// Limits which devices the monitor reports as added or removed, and so
+// which ones get auto-launched. "allow" only keeps the listed serials,
+// "deny" drops them. The device list itself always shows everything.
+export const deviceFilter = useStorage<DeviceFilter>(
+  "deviceFilter",
+  { mode: "off", serials: [] },
+  undefined,
+  {
+    mergeDefaults: true,
+  }
+);
+export const setDeviceFilter = (mode: string, serials: string[]): void => {
+  if (!["off", "allow", "deny"].includes(mode)) {
+    throw new Error(
+      `Invalid device filter ${mode}, expected off, allow or deny`
+    );
+  }
+  deviceFilter.value = {
+    mode: mode as DeviceFilterMode,
+    serials: [
+      ...new Set(serials.map((serial) => serial.trim()).filter(Boolean)),
+    ],
+  };
+};
+export const isDeviceAllowed = (serial: string): boolean => {
+  const { mode, serials } = deviceFilter.value;
+  if (mode === "off") {
+    return true;
+  }
+  return serials.includes(serial) === (mode === "allow");
+};
+
+export const MONITOR_INTERVAL_MIN = 500;
+export const MONITOR_INTERVAL_MAX = 30000;
+// milliseconds between two device monitor polls while adb works
+export const monitorInterval = useStorage<number>(
+  "monitorInterval",
+  2000,
+  undefined,
+  {
+    mergeDefaults: true,
+  }
+);
+// clamps to MONITOR_INTERVAL_MIN..MONITOR_INTERVAL_MAX and returns the value
+// actually applied
+export const setMonitorInterval = (milliseconds: number): number => {
+  if (!Number.isFinite(milliseconds)) {
+    throw new Error(`Invalid monitor interval: ${milliseconds}`);
+  }
+  monitorInterval.value = Math.min(
+    Math.max(Math.round(milliseconds), MONITOR_INTERVAL_MIN),
+    MONITOR_INTERVAL_MAX
+  );
+  return monitorInterval.value;
+};
+
+const PRESET_NAME_MAX_LENGTH = 64;
+export type ScrcpyPreset = {
+  name: string;
+  args: string[];
+  // only offered for and applied to this device when set
+  deviceId?: string;
+};
+export const scrcpyPresets = useStorage<ScrcpyPreset[]>(
+  "scrcpyPresets",
+  [],
+  undefined,
+  {
+    mergeDefaults: true,
+  }
+);
+export const getPreset = (name: string): ScrcpyPreset | undefined => {
+  return scrcpyPresets.value.find((preset) => preset.name === name.trim());
+};
+// presets usable with the device, every preset without deviceId
+export const listPresets = (deviceId?: string): ScrcpyPreset[] => {
+  return scrcpyPresets.value.filter(
+    (preset) => !deviceId || !preset.deviceId || preset.deviceId === deviceId
+  );
+};
+// Throws when the name is taken, delete the old preset to replace it.
+// A preset can't choose its device through -s/--serial, bind it instead.
+export const savePreset = (preset: ScrcpyPreset): ScrcpyPreset => {
+  const name = preset.name.trim().slice(0, PRESET_NAME_MAX_LENGTH);
+  if (!name) {
+    throw new Error("Preset name is empty");
+  }
+  if (getPreset(name)) {
+    throw new Error(`A preset named ${name} already exists`);
+  }
+  if (
+    preset.args.some(
+      (arg) => arg === "-s" || arg.startsWith("--serial") || /^-s\S/.test(arg)
+    )
+  ) {
+    throw new Error("Presets can't select a device, bind them to one instead");
+  }
+  const saved: ScrcpyPreset = {
+    name,
+    args: [...preset.args],
+    deviceId: preset.deviceId?.trim() || undefined,
+  };
+  scrcpyPresets.value = [...scrcpyPresets.value, saved];
+  return saved;
+};
+// false when there was no preset with that name
+export const deletePreset = (name: string): boolean => {
+  const presets = scrcpyPresets.value.filter(
+    (preset) => preset.name !== name.trim()
+  );
+  const deleted = presets.length !== scrcpyPresets.value.length;
+  scrcpyPresets.value = presets;
+  return deleted;
+};
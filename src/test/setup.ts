@@ -0,0 +1,25 @@
+import { beforeEach, vi } from "vitest";
+
+import { resetTauri } from "./tauri";
+
+vi.mock("@tauri-apps/api/shell", () => import("./tauri"));
+vi.mock("@tauri-apps/api/os", () => import("./tauri"));
+vi.mock("@tauri-apps/api/fs", () => import("./tauri"));
+vi.mock("@tauri-apps/api/tauri", () => import("./tauri"));
+vi.mock("@tauri-apps/api/event", () => import("./tauri"));
+vi.mock("@tauri-apps/api/http", () => import("./tauri"));
+vi.mock("@tauri-apps/api/window", () => import("./tauri"));
+vi.mock("@tauri-apps/api/clipboard", () => import("./tauri"));
+
+// storage.ts reads localStorage directly and node has none
+const items = new Map<string, string>();
+vi.stubGlobal("localStorage", {
+  getItem: (key: string) => items.get(key) ?? null,
+  setItem: (key: string, value: string) => items.set(key, String(value)),
+  removeItem: (key: string) => items.delete(key),
+  clear: () => items.clear(),
+});
+
+beforeEach(() => {
+  resetTauri();
+});
@@ -0,0 +1,124 @@
+// Stand-ins for the Tauri APIs, which only answer inside the webview. Tests
+// script adb through `execute`, hold back scrcpy launches with `spawnGate`
+// and look at what was started in `commands`.
+import { vi } from "vitest";
+
+type Handler = (...args: any[]) => void;
+class Emitter {
+  handlers = new Map<string, Handler[]>();
+  on(event: string, handler: Handler): this {
+    this.handlers.set(event, [...(this.handlers.get(event) || []), handler]);
+    return this;
+  }
+  emit(event: string, ...args: unknown[]): void {
+    (this.handlers.get(event) || []).forEach((handler) => handler(...args));
+  }
+}
+
+export type CommandOutput = { code: number; stdout?: string; stderr?: string };
+// serials `adb devices -l` lists
+export const attachedDevices: string[] = [];
+// what `adb -s <serial> shell getprop <name>` prints, for any serial
+export const deviceProps: Record<string, string> = {};
+const defaultExecute = (_program: string, args: string[]): CommandOutput => {
+  if (args[0] === "devices") {
+    const lines = attachedDevices.map((serial) => `${serial}\tdevice\n`);
+    return { code: 0, stdout: `List of devices attached\n${lines.join("")}` };
+  }
+  if (args[2] === "shell" && args[3] === "getprop" && args[4]) {
+    return { code: 0, stdout: `${deviceProps[args[4]] || ""}\n` };
+  }
+  return { code: 0 };
+};
+export const execute = vi.fn(defaultExecute);
+// awaited by every spawn, resolve it later to keep a launch "starting"
+export const spawnGate = vi.fn(async (_command: Command): Promise<void> => {});
+export const commands: Command[] = [];
+
+export class Command extends Emitter {
+  program: string;
+  args: string[];
+  options: { env?: Record<string, string> };
+  stdout = new Emitter();
+  stderr = new Emitter();
+  child?: Child;
+  constructor(
+    program: string,
+    args: string | string[] = [],
+    options: { env?: Record<string, string> } = {}
+  ) {
+    super();
+    this.program = program;
+    this.args = typeof args === "string" ? [args] : args;
+    this.options = options;
+    commands.push(this);
+  }
+  async execute() {
+    const { code, stdout = "", stderr = "" } = execute(this.program, this.args);
+    return { code, signal: null, stdout, stderr };
+  }
+  async spawn(): Promise<Child> {
+    await spawnGate(this);
+    this.child = new Child(this);
+    return this.child;
+  }
+  // the process exited
+  close(code: number | null = 0, signal: number | null = null): void {
+    this.emit("close", { code, signal });
+  }
+}
+export class Child {
+  pid = 1;
+  killed = false;
+  command: Command;
+  constructor(command: Command) {
+    this.command = command;
+  }
+  async kill(): Promise<void> {
+    this.killed = true;
+    // like a real process, 'close' follows the kill a little later
+    queueMicrotask(() => this.command.close(null, 9));
+  }
+  async write(): Promise<void> {}
+}
+
+const defaultInvoke = async (
+  command: string,
+  _args?: Record<string, unknown>
+): Promise<unknown> => {
+  switch (command) {
+    case "get_tool_paths":
+      return {
+        adb: "/usr/bin/adb",
+        scrcpy: "/usr/bin/scrcpy",
+        adbEnv: null,
+        searchPath: "/usr/bin",
+      };
+    case "is_file":
+      return true;
+    default:
+      return null;
+  }
+};
+export const invoke = vi.fn(defaultInvoke);
+
+export const type = vi.fn(async () => "Linux");
+export const arch = vi.fn(async () => "x86_64");
+export const version = vi.fn(async () => "6.1.0");
+export const listen = vi.fn(async () => () => {});
+export const writeTextFile = vi.fn(async () => {});
+export const fetch = vi.fn();
+export const availableMonitors = vi.fn(async () => []);
+export const writeText = vi.fn(async () => {});
+
+export const resetTauri = (): void => {
+  attachedDevices.length = 0;
+  Object.keys(deviceProps).forEach((name) => delete deviceProps[name]);
+  commands.length = 0;
+  execute.mockReset();
+  execute.mockImplementation(defaultExecute);
+  spawnGate.mockReset();
+  spawnGate.mockImplementation(async () => {});
+  invoke.mockReset();
+  invoke.mockImplementation(defaultInvoke);
+};
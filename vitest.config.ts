@@ -0,0 +1,9 @@
+import { defineConfig } from "vitest/config";
+
+export default defineConfig({
+  test: {
+    include: ["src/**/*.test.ts"],
+    // the Tauri APIs only exist inside the webview, see src/test/tauri.ts
+    setupFiles: ["src/test/setup.ts"],
+  },
+});